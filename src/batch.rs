@@ -0,0 +1,238 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use socket2::Type;
+
+use crate::cancel::CancelToken;
+use crate::errors::Error;
+use crate::otel::Parent;
+use crate::ping::{Ping, PingReply};
+
+const DEFAULT_CONCURRENCY: usize = 64;
+
+/// The result of pinging one target of a batch.
+#[derive(Debug)]
+pub enum PingOutcome {
+    Reply(PingReply),
+    /// No reply arrived before the timeout. Only used unless
+    /// [`Batch::timeout_as_error`] is set.
+    NoReply,
+    Error(Error),
+}
+
+impl PingOutcome {
+    fn from_result(result: Result<PingReply, Error>, timeout_as_error: bool) -> Self {
+        match result {
+            Ok(reply) => PingOutcome::Reply(reply),
+            Err(error) if error.is_timeout() && !timeout_as_error => PingOutcome::NoReply,
+            Err(error) => PingOutcome::Error(error),
+        }
+    }
+
+    pub fn is_reply(&self) -> bool {
+        matches!(self, PingOutcome::Reply(_))
+    }
+
+    pub fn reply(&self) -> Option<&PingReply> {
+        match self {
+            PingOutcome::Reply(reply) => Some(reply),
+            _ => None,
+        }
+    }
+
+    /// Converts the outcome into the result a single ping would have returned,
+    /// turning `NoReply` back into a timeout error.
+    pub fn into_result(self) -> Result<PingReply, Error> {
+        match self {
+            PingOutcome::Reply(reply) => Ok(reply),
+            PingOutcome::NoReply => {
+                let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout occured");
+                Err(Error::IoError { error })
+            }
+            PingOutcome::Error(error) => Err(error),
+        }
+    }
+}
+
+impl From<PingOutcome> for Result<PingReply, Error> {
+    fn from(outcome: PingOutcome) -> Self {
+        outcome.into_result()
+    }
+}
+
+/// Pings many targets concurrently with the same options.
+#[derive(Debug, Clone)]
+pub struct Batch<'a> {
+    ping: Ping<'a>,
+    concurrency: usize,
+    timeout_as_error: bool,
+    slot_idents: Option<u16>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new() -> Self {
+        Batch {
+            ping: Ping::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout_as_error: false,
+            slot_idents: None,
+        }
+    }
+
+    /// Sets the options every target is pinged with, e.g.
+    /// `batch.configure(|ping| ping.timeout(timeout).ttl(32))`.
+    pub fn configure(mut self, configure: impl FnOnce(Ping<'a>) -> Ping<'a>) -> Self {
+        self.ping = configure(self.ping);
+        self
+    }
+
+    /// Sets how many targets are pinged at the same time. Every ping in flight uses
+    /// its own thread and socket. Defaults to 64.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Reports timeouts as [`PingOutcome::Error`] instead of
+    /// [`PingOutcome::NoReply`].
+    pub fn timeout_as_error(mut self, timeout_as_error: bool) -> Self {
+        self.timeout_as_error = timeout_as_error;
+        self
+    }
+
+    /// Assigns idents per concurrency slot instead of per probe: the slot `i`
+    /// (`0..concurrency`) pings with ident `base + i`, wrapping at `u16::MAX`, and
+    /// reuses it for the next target once its ping finished. Each slot counts its
+    /// sequence numbers up from the configured one, so `(ident, seq_cnt)` stays
+    /// unique within the batch. This keeps idents predictable, e.g. for firewall
+    /// rules, and lets a reply be traced back to its slot as `ident - base`.
+    ///
+    /// The idents are reserved in the process wide in-flight registry while a
+    /// probe is out, so automatically allocated idents of other pings skip them.
+    ///
+    /// On Linux the kernel replaces the ident of DGRAM echo requests with the
+    /// socket's local port, so slot idents need raw sockets there: pings with the
+    /// default DGRAM socket type fail with [`Error::InvalidProtocol`].
+    pub fn slot_idents(mut self, base: u16) -> Self {
+        self.slot_idents = Some(base);
+        self
+    }
+
+    /// Pings every address and returns the outcomes in the order of `addrs`.
+    ///
+    /// If the pings have a [`cancel_token`](Ping::cancel_token), cancelling it
+    /// abandons the pings in flight and skips the targets not pinged yet, which
+    /// all come back as [`PingOutcome::Error`] with [`Error::Cancelled`]. An
+    /// abandoned ping releases its ident from the in-flight registry and closes
+    /// its socket as it returns, so a late reply is never matched to it or to a
+    /// later ping reusing the ident.
+    pub fn ping_many(&self, addrs: &[IpAddr]) -> Vec<PingOutcome> {
+        let pings = addrs
+            .iter()
+            .map(|addr| self.ping.clone().addr(*addr))
+            .collect();
+        self.run(pings)
+    }
+
+    /// Runs every ping with its own options, e.g. a different TTL or interface per
+    /// target, instead of the ones set with [`configure`](Batch::configure), and
+    /// returns the outcomes in the order of `pings`. The socket of each ping is
+    /// opened for its own target and socket type, so IPv4 and IPv6 targets and
+    /// raw and DGRAM sockets can be mixed. The concurrency, timeout handling and
+    /// slot idents of the batch still apply; slot idents replace the idents and
+    /// sequence numbers of the pings.
+    ///
+    /// As with [`ping_many`](Batch::ping_many), every ping in flight uses its own
+    /// thread and socket, and the socket is closed as soon as its ping finished,
+    /// so at most `concurrency` sockets are open at a time.
+    pub fn ping_many_configured(&self, pings: Vec<Ping<'a>>) -> Vec<PingOutcome> {
+        self.run(pings)
+    }
+
+    fn run(&self, pings: Vec<Ping<'a>>) -> Vec<PingOutcome> {
+        crate::otel::in_batch_span(pings.len(), || self.run_in_span(pings))
+    }
+
+    fn run_in_span(&self, pings: Vec<Ping<'a>>) -> Vec<PingOutcome> {
+        // the worker threads record their pings under the caller's context
+        let parent = Parent::capture();
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..pings.len()).map(|_| None).collect::<Vec<_>>());
+
+        thread::scope(|scope| {
+            for slot in 0..self.concurrency.min(pings.len()) {
+                let next = &next;
+                let results = &results;
+                let pings = &pings;
+                let parent = &parent;
+                let mut seq_cnt = self.ping.seq_cnt.unwrap_or(1);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let mut ping = match pings.get(index) {
+                        Some(ping) => ping.clone(),
+                        None => return,
+                    };
+                    if let Some(base) = self.slot_idents {
+                        ping = ping.ident(base.wrapping_add(slot as u16)).seq_cnt(seq_cnt);
+                        seq_cnt = seq_cnt.wrapping_add(1);
+                    }
+                    let result = if ping.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                        // not even opening a socket for targets left after cancelling
+                        Err(Error::Cancelled)
+                    } else if self.slot_idents.is_some()
+                        && ping.socket_type == Type::DGRAM
+                        && cfg!(target_os = "linux")
+                    {
+                        // the slot ident would never reach the wire
+                        Err(Error::InvalidProtocol)
+                    } else {
+                        parent.clone().in_scope(|| ping.ping())
+                    };
+                    let outcome = PingOutcome::from_result(result, self.timeout_as_error);
+                    if let Ok(mut results) = results.lock() {
+                        results[index] = Some(outcome);
+                    }
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|outcome| outcome.unwrap_or(PingOutcome::Error(Error::InternalError)))
+            .collect()
+    }
+}
+
+impl Default for Batch<'_> {
+    fn default() -> Self {
+        Batch::new()
+    }
+}
+
+/// Pings every address concurrently with default options, see [`Batch`].
+pub fn ping_many(addrs: &[IpAddr]) -> Vec<PingOutcome> {
+    Batch::new().ping_many(addrs)
+}
+
+/// Runs every ping concurrently with its own options, see
+/// [`Batch::ping_many_configured`].
+pub fn ping_many_configured(pings: Vec<Ping>) -> Vec<PingOutcome> {
+    Batch::new().ping_many_configured(pings)
+}
+
+/// Pairs the outcomes of [`Batch::ping_many`] with their addresses and orders them
+/// by ascending RTT. Targets without a reply, whether they timed out or failed,
+/// come last in their original order.
+pub fn sort_by_rtt(addrs: &[IpAddr], outcomes: Vec<PingOutcome>) -> Vec<(IpAddr, PingOutcome)> {
+    let mut sorted: Vec<_> = addrs.iter().copied().zip(outcomes).collect();
+    sorted.sort_by_key(|(_, outcome)| match outcome.reply() {
+        Some(reply) => (false, reply.rtt),
+        None => (true, Duration::ZERO),
+    });
+    sorted
+}
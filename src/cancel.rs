@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a ping waiting for its reply checks whether it was cancelled.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A flag to stop pings, streams and batches early, see
+/// [`Ping::cancel_token`](crate::Ping::cancel_token). Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Cancels every ping using this token. This cannot be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Returns a token that is cancelled when the process receives `SIGINT`, e.g.
+/// because the user pressed Ctrl-C, so a command line tool can stop a long
+/// stream or batch and still print the statistics of the pings done so far.
+///
+/// The first call installs a signal handler that replaces the default one, so
+/// Ctrl-C no longer terminates the process; later calls return the same token.
+/// Only available with the `ctrl-c` feature on Unix.
+#[cfg(all(feature = "ctrl-c", unix))]
+pub fn cancel_on_ctrl_c() -> Result<CancelToken, crate::Error> {
+    use std::sync::OnceLock;
+
+    static TOKEN: OnceLock<CancelToken> = OnceLock::new();
+
+    extern "C" fn handle_sigint(_signal: libc::c_int) {
+        // only touches an atomic, which is safe in a signal handler
+        if let Some(token) = TOKEN.get() {
+            token.cancel();
+        }
+    }
+
+    if let Some(token) = TOKEN.get() {
+        return Ok(token.clone());
+    }
+    let token = TOKEN.get_or_init(CancelToken::new).clone();
+    let handler = handle_sigint as extern "C" fn(libc::c_int);
+    // SAFETY: the handler is async signal safe
+    if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(token)
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::clock::clock_resolution;
+
+/// What this build of the crate supports and which sockets can be opened on this
+/// host, see [`crate_capabilities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Capabilities {
+    /// Version of the crate.
+    pub version: &'static str,
+    /// Whether the `otel` feature is compiled in.
+    pub otel: bool,
+    /// Whether the `serde` feature is compiled in.
+    pub serde: bool,
+    /// Whether [`cancel_on_ctrl_c`](crate::cancel_on_ctrl_c) is available, which
+    /// needs the `ctrl-c` feature and Unix.
+    pub ctrl_c: bool,
+    /// Whether replies are waited for with `poll(2)`, which needs the `poll`
+    /// feature and Unix.
+    pub poll: bool,
+    /// Whether the `testing` feature is compiled in.
+    pub testing: bool,
+    /// Whether [`Ping::dont_fragment`](crate::Ping::dont_fragment),
+    /// [`Ping::vrf`](crate::Ping::vrf) and IPv6 extension headers are supported
+    /// on this platform.
+    pub linux_socket_options: bool,
+    /// Whether a raw IPv4 ICMP socket could be opened, which usually needs root or
+    /// `CAP_NET_RAW`.
+    pub raw_v4: bool,
+    pub raw_v6: bool,
+    /// Whether an unprivileged DGRAM IPv4 ICMP socket could be opened, which on
+    /// Linux depends on `net.ipv4.ping_group_range`.
+    pub dgram_v4: bool,
+    pub dgram_v6: bool,
+    /// See [`clock_resolution`](crate::clock_resolution).
+    pub clock_resolution: Duration,
+}
+
+/// Reports the features compiled into this crate and probes which ICMP sockets
+/// the current process can open, e.g. to pick a socket type or to include in bug
+/// reports. Each probe opens and closes a socket; nothing is sent.
+pub fn crate_capabilities() -> Capabilities {
+    let can_open =
+        |domain, socket_type, protocol| Socket::new(domain, socket_type, Some(protocol)).is_ok();
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        otel: cfg!(feature = "otel"),
+        serde: cfg!(feature = "serde"),
+        ctrl_c: cfg!(all(feature = "ctrl-c", unix)),
+        poll: cfg!(all(feature = "poll", unix)),
+        testing: cfg!(feature = "testing"),
+        linux_socket_options: cfg!(target_os = "linux"),
+        raw_v4: can_open(Domain::IPV4, Type::RAW, Protocol::ICMPV4),
+        raw_v6: can_open(Domain::IPV6, Type::RAW, Protocol::ICMPV6),
+        dgram_v4: can_open(Domain::IPV4, Type::DGRAM, Protocol::ICMPV4),
+        dgram_v6: can_open(Domain::IPV6, Type::DGRAM, Protocol::ICMPV6),
+        clock_resolution: clock_resolution(),
+    }
+}
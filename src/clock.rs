@@ -0,0 +1,31 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const SAMPLES: usize = 16;
+
+/// Estimates the resolution of [`Instant`], the clock RTTs are measured with, as
+/// the smallest step between consecutive readings. Most platforms measure in
+/// nanoseconds, but some only advance the clock every few milliseconds, so
+/// shorter RTTs cannot be told apart there.
+///
+/// The estimate is taken once, on first use, by reading the clock in a tight
+/// loop, and may come out larger than the real resolution if the thread is
+/// preempted meanwhile.
+pub fn clock_resolution() -> Duration {
+    static RESOLUTION: OnceLock<Duration> = OnceLock::new();
+
+    *RESOLUTION.get_or_init(|| {
+        (0..SAMPLES)
+            .map(|_| {
+                let start = Instant::now();
+                loop {
+                    let step = Instant::now() - start;
+                    if !step.is_zero() {
+                        return step;
+                    }
+                }
+            })
+            .min()
+            .unwrap_or_default()
+    })
+}
@@ -0,0 +1,176 @@
+use socket2::{Socket, Type};
+
+/// System wide kernel counters of incoming IP and ICMP packets that were dropped
+/// before reaching a socket, summed over IPv4 and IPv6.
+///
+/// Reading the counters before and after a run and comparing them tells local
+/// drops apart from losses on the network, see
+/// [`PingStatistics::local_drops`](crate::PingStatistics::local_drops). The
+/// counters are best effort: they are only available on Linux, where they come
+/// from `/proc/net/snmp` and `/proc/net/snmp6`, and they count packets of every
+/// process and every host, not just our replies. Replies a socket drops because
+/// its receive buffer is full are not covered; a burst counts those per socket
+/// with [`socket_drops`] and adds them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DropCounters {
+    /// IP packets discarded without an error in them, e.g. for lack of memory.
+    pub ip_in_discards: u64,
+    /// ICMP messages that were malformed or had a bad checksum.
+    pub icmp_in_errors: u64,
+}
+
+impl DropCounters {
+    /// Reads the current counters, or returns `None` if they are not available.
+    pub fn read() -> Option<Self> {
+        let snmp = std::fs::read_to_string("/proc/net/snmp").ok()?;
+        let snmp6 = std::fs::read_to_string("/proc/net/snmp6").unwrap_or_default();
+        let v4 = |protocol, name| snmp_counter(&snmp, protocol, name).unwrap_or(0);
+        let v6 = |name| snmp6_counter(&snmp6, name).unwrap_or(0);
+        Some(DropCounters {
+            ip_in_discards: v4("Ip:", "InDiscards") + v6("Ip6InDiscards"),
+            icmp_in_errors: v4("Icmp:", "InErrors") + v6("Icmp6InErrors"),
+        })
+    }
+
+    /// Total number of drops counted since `earlier` was read.
+    pub fn drops_since(&self, earlier: &DropCounters) -> u64 {
+        self.ip_in_discards.saturating_sub(earlier.ip_in_discards)
+            + self.icmp_in_errors.saturating_sub(earlier.icmp_in_errors)
+    }
+}
+
+/// Packets `socket` dropped because its receive buffer was full, from the `drops`
+/// column of its entry in `/proc/net/raw`, `/proc/net/icmp` or their IPv6
+/// counterparts, which is found by the inode of the socket. Only available on
+/// Linux.
+#[cfg(target_os = "linux")]
+pub(crate) fn socket_drops(socket: &Socket, is_ipv4: bool, socket_type: Type) -> Option<u64> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: fstat only writes the stat struct, which outlives the call
+    let inode = unsafe {
+        let mut stat: libc::stat = std::mem::zeroed();
+        if libc::fstat(socket.as_raw_fd(), &mut stat) == -1 {
+            return None;
+        }
+        stat.st_ino as u64
+    };
+    let table = match (is_ipv4, socket_type == Type::RAW) {
+        (true, true) => "/proc/net/raw",
+        (true, false) => "/proc/net/icmp",
+        (false, true) => "/proc/net/raw6",
+        (false, false) => "/proc/net/icmp6",
+    };
+    let table = std::fs::read_to_string(table).ok()?;
+    table_drops(&table, inode)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn socket_drops(_socket: &Socket, _is_ipv4: bool, _socket_type: Type) -> Option<u64> {
+    None
+}
+
+/// Looks up the `drops` of the socket with `inode` in a socket table like
+/// `/proc/net/raw`, which has a header line and then one line per socket.
+fn table_drops(table: &str, inode: u64) -> Option<u64> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(9)?.parse::<u64>().ok()? == inode {
+            fields.get(12)?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Looks up a counter of `/proc/net/snmp`, where each protocol has a line of names
+/// followed by a line of values.
+fn snmp_counter(snmp: &str, protocol: &str, name: &str) -> Option<u64> {
+    let mut lines = snmp.lines().filter(|line| line.starts_with(protocol));
+    let names = lines.next()?;
+    let values = lines.next()?;
+    let index = names.split_whitespace().position(|field| field == name)?;
+    values.split_whitespace().nth(index)?.parse().ok()
+}
+
+/// Looks up a counter of `/proc/net/snmp6`, which has one name and value per line.
+fn snmp6_counter(snmp6: &str, name: &str) -> Option<u64> {
+    snmp6.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? == name {
+            fields.next()?.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNMP: &str = "\
+Ip: Forwarding DefaultTTL InReceives InHdrErrors InAddrErrors ForwDatagrams InUnknownProtos InDiscards InDelivers OutRequests
+Ip: 2 64 15900 0 0 0 0 7 15900 16169
+Icmp: InMsgs InErrors InCsumErrors InDestUnreachs
+Icmp: 8281 3 0 222
+IcmpMsg: InType0 InType3 OutType8
+IcmpMsg: 4029 222 4252
+Udp: InDatagrams NoPorts InErrors
+Udp: 10 2 5
+";
+
+    const SNMP6: &str = "\
+Ip6InReceives                   \t1644
+Ip6InDiscards                   \t4
+Icmp6InMsgs                     \t30
+Icmp6InErrors                   \t1
+";
+
+    #[test]
+    fn snmp_counters() {
+        assert_eq!(snmp_counter(SNMP, "Ip:", "InDiscards"), Some(7));
+        assert_eq!(snmp_counter(SNMP, "Icmp:", "InErrors"), Some(3));
+        // other protocols with a counter of the same name are not mixed up
+        assert_eq!(snmp_counter(SNMP, "Udp:", "InErrors"), Some(5));
+        assert_eq!(snmp_counter(SNMP, "Icmp:", "Missing"), None);
+        assert_eq!(snmp_counter(SNMP, "Tcp:", "InErrors"), None);
+    }
+
+    const RAW: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+    1: 00000000:0001 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 48211 2 0000000000000000 0
+    1: 00000000:0001 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 48307 2 0000000000000000 17
+";
+
+    #[test]
+    fn socket_table_drops() {
+        assert_eq!(table_drops(RAW, 48211), Some(0));
+        assert_eq!(table_drops(RAW, 48307), Some(17));
+        assert_eq!(table_drops(RAW, 1), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn open_socket_drops() {
+        let socket = Socket::new(
+            socket2::Domain::IPV4,
+            Type::DGRAM,
+            Some(socket2::Protocol::ICMPV4),
+        )
+        .unwrap();
+        // sockets are only listed once bound
+        assert_eq!(socket_drops(&socket, true, Type::DGRAM), None);
+        let local = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        socket.bind(&local.into()).unwrap();
+        assert_eq!(socket_drops(&socket, true, Type::DGRAM), Some(0));
+    }
+
+    #[test]
+    fn snmp6_counters() {
+        assert_eq!(snmp6_counter(SNMP6, "Ip6InDiscards"), Some(4));
+        assert_eq!(snmp6_counter(SNMP6, "Icmp6InErrors"), Some(1));
+        assert_eq!(snmp6_counter(SNMP6, "Icmp6InCsumErrors"), None);
+        assert_eq!(snmp6_counter("", "Ip6InDiscards"), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::ping::PingReply;
+
+/// Whether the name of an address resolves back to it, see
+/// [`Ping::ping_fcrdns`](crate::Ping::ping_fcrdns).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fcrdns {
+    /// The PTR name of the address resolves back to it.
+    Confirmed { name: String },
+    /// The PTR name of the address resolves to `addrs`, which do not include it.
+    Mismatch { name: String, addrs: Vec<IpAddr> },
+    /// The address has no PTR record.
+    NoName,
+    /// A lookup failed, or both did not finish within the timeout.
+    Failed,
+}
+
+/// The outcome of [`Ping::ping_fcrdns`](crate::Ping::ping_fcrdns).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FcrdnsReply {
+    pub reply: PingReply,
+    pub fcrdns: Fcrdns,
+}
+
+/// Checks whether the PTR name of `addr` resolves back to it, giving up after
+/// `timeout`.
+pub(crate) fn fcrdns(addr: IpAddr, timeout: Duration) -> Fcrdns {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let fcrdns = match reverse_lookup(addr) {
+            Ok(Some(name)) => match (name.as_str(), 0).to_socket_addrs() {
+                Ok(addrs) => {
+                    let addrs: Vec<IpAddr> = addrs.map(|addr| addr.ip()).collect();
+                    if addrs.contains(&addr) {
+                        Fcrdns::Confirmed { name }
+                    } else {
+                        Fcrdns::Mismatch { name, addrs }
+                    }
+                }
+                Err(_) => Fcrdns::Failed,
+            },
+            Ok(None) => Fcrdns::NoName,
+            Err(_) => Fcrdns::Failed,
+        };
+        // the receiver is gone if the lookups timed out
+        let _ = sender.send(fcrdns);
+    });
+    receiver.recv_timeout(timeout).unwrap_or(Fcrdns::Failed)
+}
+
+/// Looks up the PTR name of `addr`, returning `None` if it has none.
+#[cfg(unix)]
+fn reverse_lookup(addr: IpAddr) -> Result<Option<String>, Error> {
+    use std::ffi::CStr;
+
+    let addr = socket2::SockAddr::from(SocketAddr::new(addr, 0));
+    let mut host: [libc::c_char; libc::NI_MAXHOST as usize] = [0; libc::NI_MAXHOST as usize];
+    // SAFETY: the address and the host buffer are valid for their given lengths
+    let result = unsafe {
+        libc::getnameinfo(
+            addr.as_ptr(),
+            addr.len(),
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            std::ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    };
+    match result {
+        0 => {
+            // SAFETY: getnameinfo wrote a NUL terminated string into host
+            let name = unsafe { CStr::from_ptr(host.as_ptr()) };
+            Ok(Some(name.to_string_lossy().into_owned()))
+        }
+        libc::EAI_NONAME => Ok(None),
+        _ => {
+            let error = std::io::Error::other("reverse lookup failed");
+            Err(Error::IoError { error })
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn reverse_lookup(_addr: IpAddr) -> Result<Option<String>, Error> {
+    let error = std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reverse lookups are only supported on Unix",
+    );
+    Err(Error::IoError { error })
+}
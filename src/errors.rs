@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -10,6 +12,59 @@ pub enum Error {
     DecodeV4Error,
     #[error("Decode echo reply error occurred while processing the ICMP echo reply.")]
     DecodeEchoReplyError,
+    #[error("Decode ICMP error occurred while processing the ICMP message.")]
+    DecodeIcmpError,
+    /// The destination was reported unreachable by `sender`. `original` holds the
+    /// copy of our echo request embedded in the ICMP error, starting at its IP
+    /// header.
+    #[error("destination unreachable (code {code}) reported by {sender}")]
+    Unreachable {
+        code: u8,
+        sender: IpAddr,
+        original: Vec<u8>,
+    },
+    /// The TTL of our echo request ran out at the router `sender` on the way to the
+    /// destination. `original` is the embedded copy of the request, as for
+    /// `Unreachable`.
+    #[error("time exceeded (code {code}) reported by {sender}")]
+    TimeExceeded {
+        code: u8,
+        sender: IpAddr,
+        original: Vec<u8>,
+    },
+    /// Our echo request was larger than the MTU `mtu` of a link on the path and
+    /// could not be fragmented. `sender` and `original` are as for `Unreachable`.
+    #[error("packet too big for MTU {mtu} reported by {sender}")]
+    PacketTooBig {
+        mtu: u32,
+        sender: IpAddr,
+        original: Vec<u8>,
+    },
+    /// The source address the kernel picks for the target changed from `before` to
+    /// `after` while waiting for the reply, see
+    /// [`Ping::route_check`](crate::Ping::route_check). `after` is `None` if no
+    /// route to the target is left.
+    #[error("route changed while waiting for the reply, source {before} is now {after:?}")]
+    RouteChanged {
+        before: IpAddr,
+        after: Option<IpAddr>,
+    },
+    /// The target `addr` is the local host, which was not allowed with
+    /// [`Ping::allow_self`](crate::Ping::allow_self).
+    #[error("{addr} is an address of the local host")]
+    LocalTarget { addr: IpAddr },
+    /// `input` is neither an IP address, a CIDR range nor a hostname.
+    #[error("invalid target {input:?}")]
+    InvalidTarget { input: String },
+    /// A target expanded to more addresses than the `limit` that was left.
+    #[error("target expands to more than {limit} addresses")]
+    TooManyTargets { limit: usize },
+    #[error("cancelled")]
+    Cancelled,
+    #[error("operation would block")]
+    WouldBlock,
+    #[error("too many packets that are not our reply")]
+    TooManyForeignPackets,
     #[error("io error: {error}")]
     IoError {
         #[from]
@@ -17,3 +72,62 @@ pub enum Error {
         error: ::std::io::Error,
     },
 }
+
+impl Error {
+    /// Whether the error is expected to go away on its own, like a lost reply or a
+    /// network that is unreachable while a link is down. Other errors, for example
+    /// failing to create the socket, will keep happening on every retry.
+    pub fn is_transient(&self) -> bool {
+        use std::io::ErrorKind;
+
+        match self {
+            Error::Unreachable { .. }
+            | Error::TimeExceeded { .. }
+            | Error::PacketTooBig { .. }
+            | Error::RouteChanged { .. }
+            | Error::WouldBlock
+            | Error::TooManyForeignPackets => true,
+            Error::IoError { error } => matches!(
+                error.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::Interrupted
+                    | ErrorKind::HostUnreachable
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::NetworkDown
+                    | ErrorKind::AddrNotAvailable
+            ),
+            _ => false,
+        }
+    }
+
+    /// The OS error number of an I/O error, e.g. of a failed send, for correlating
+    /// with kernel logs.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Error::IoError { error } => error.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// Whether no reply arrived before the timeout.
+    pub fn is_timeout(&self) -> bool {
+        use std::io::ErrorKind;
+
+        // blocking reads that time out report `WouldBlock` on Unix
+        match self {
+            Error::IoError { error } => {
+                matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock)
+            }
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn map_would_block(error: ::std::io::Error) -> Error {
+    if error.kind() == ::std::io::ErrorKind::WouldBlock {
+        Error::WouldBlock
+    } else {
+        Error::from(error)
+    }
+}
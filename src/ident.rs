@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::sync::{Mutex, OnceLock};
+
+use rand::Rng;
+
+/// Hands out idents for echo requests while keeping track of which
+/// `(ident, seq_cnt)` pairs are still in flight.
+///
+/// Idents are handed out in order, wrapping around at the end of the range. A
+/// pair that is still in flight is skipped, so once the range wraps a reply can
+/// never be attributed to the wrong probe.
+#[derive(Debug)]
+pub struct IdentAllocator {
+    range: RangeInclusive<u16>,
+    next: u16,
+    in_flight: HashSet<(u16, u16)>,
+}
+
+impl IdentAllocator {
+    /// Creates an allocator for all idents, starting at a random one.
+    pub fn new() -> Self {
+        let next = rand::thread_rng().gen();
+        IdentAllocator {
+            range: 0..=u16::MAX,
+            next,
+            in_flight: HashSet::new(),
+        }
+    }
+
+    /// Creates an allocator that only hands out idents from `range`.
+    pub fn with_range(range: RangeInclusive<u16>) -> Self {
+        IdentAllocator {
+            next: *range.start(),
+            range,
+            in_flight: HashSet::new(),
+        }
+    }
+
+    /// Returns the next ident whose pair with `seq_cnt` is not in flight and
+    /// marks that pair as in flight. Returns `None` when every ident of the range
+    /// is in flight with `seq_cnt`.
+    pub fn allocate(&mut self, seq_cnt: u16) -> Option<u16> {
+        let size = usize::from(*self.range.end() - *self.range.start()) + 1;
+        for _ in 0..size {
+            let ident = self.next;
+            self.next = if ident >= *self.range.end() {
+                *self.range.start()
+            } else {
+                ident + 1
+            };
+            if self.in_flight.insert((ident, seq_cnt)) {
+                return Some(ident);
+            }
+        }
+        None
+    }
+
+    /// Marks a pair with an ident chosen by the caller as in flight. Returns
+    /// `false` if it already was.
+    pub fn reserve(&mut self, ident: u16, seq_cnt: u16) -> bool {
+        self.in_flight.insert((ident, seq_cnt))
+    }
+
+    /// Marks the pair as no longer in flight.
+    pub fn release(&mut self, ident: u16, seq_cnt: u16) {
+        self.in_flight.remove(&(ident, seq_cnt));
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+impl Default for IdentAllocator {
+    fn default() -> Self {
+        IdentAllocator::new()
+    }
+}
+
+fn global() -> &'static Mutex<IdentAllocator> {
+    static ALLOCATOR: OnceLock<Mutex<IdentAllocator>> = OnceLock::new();
+    ALLOCATOR.get_or_init(|| Mutex::new(IdentAllocator::new()))
+}
+
+/// An ident allocated from the process wide allocator, released when dropped.
+#[derive(Debug)]
+pub(crate) struct InFlight {
+    pub(crate) ident: u16,
+    seq_cnt: u16,
+}
+
+impl InFlight {
+    pub(crate) fn allocate(seq_cnt: u16) -> Option<Self> {
+        let ident = global().lock().ok()?.allocate(seq_cnt)?;
+        Some(InFlight { ident, seq_cnt })
+    }
+
+    /// Reserves an ident chosen by the caller. Returns `None` if the pair is
+    /// already in flight.
+    pub(crate) fn reserve(ident: u16, seq_cnt: u16) -> Option<Self> {
+        if global().lock().ok()?.reserve(ident, seq_cnt) {
+            Some(InFlight { ident, seq_cnt })
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut allocator) = global().lock() {
+            allocator.release(self.ident, self.seq_cnt);
+        }
+    }
+}
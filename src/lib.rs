@@ -1,8 +1,54 @@
+mod batch;
+mod cancel;
+mod capabilities;
+mod clock;
+mod counters;
+mod dns;
 mod errors;
+mod ident;
+mod matching;
+mod message;
+mod monitor;
+mod otel;
 mod packet;
+mod path;
 mod ping;
+mod pool;
+mod record;
+mod socket;
+mod stats;
+mod stream;
+mod targets;
+mod tcp;
 
+pub use crate::batch::{ping_many, ping_many_configured, sort_by_rtt, Batch, PingOutcome};
+#[cfg(all(feature = "ctrl-c", unix))]
+pub use crate::cancel::cancel_on_ctrl_c;
+pub use crate::cancel::CancelToken;
+pub use crate::capabilities::{crate_capabilities, Capabilities};
+pub use crate::clock::clock_resolution;
+pub use crate::counters::DropCounters;
+pub use crate::dns::{Fcrdns, FcrdnsReply};
 pub use crate::errors::Error;
-pub use crate::ping::Ping;
+pub use crate::ident::IdentAllocator;
+#[cfg(feature = "testing")]
+pub use crate::matching::{decide_match, MatchDecision, MatchStrategy, RequestMeta};
+pub use crate::message::{write_checksum, IcmpMessage, IcmpReply, IcmpVersion, RawIcmp};
+pub use crate::monitor::{Monitor, MonitorTarget, TargetHealth};
+pub use crate::path::{
+    detect_pmtu_blackhole, find_loss_threshold, find_loss_threshold_with, first_hop, path_symmetry,
+    FirstHop, LossThreshold, LossThresholdSearch, PathSymmetry, PmtuVerdict,
+};
 pub use crate::ping::{dgramsock, ping, rawsock};
+pub use crate::ping::{BindOrder, IcmpEvent, Ping, PingReply};
+pub use crate::pool::PingPool;
+pub use crate::record::{EventKind, PingEvent, RecordedEvent, Recorder};
+pub use crate::socket::{EchoPacket, PingSocket};
+pub use crate::stats::{
+    Burst, ConfidenceTarget, HealthStatus, HealthThresholds, PingStatistics, QualityModel,
+    RateLimitHeuristic,
+};
+pub use crate::stream::PingStream;
+pub use crate::targets::{TargetSet, Targets};
+pub use crate::tcp::{tcp_scan, PortState};
 pub use socket2::Type;
@@ -0,0 +1,130 @@
+use std::net::IpAddr;
+
+use socket2::Type;
+
+use crate::errors::Error;
+use crate::packet::{EchoReply, ErrorKind, ErrorMessage, IcmpV4, IcmpV6};
+use crate::ping::{icmp_data, original_ident};
+
+/// The echo request a received packet is checked against by [`decide_match`].
+#[derive(Debug, Clone, Copy)]
+pub struct RequestMeta<'a> {
+    /// Whether the request went to an IPv4 target.
+    pub is_ipv4: bool,
+    /// Type of the socket the packet was received on. Packets received on raw
+    /// IPv4 sockets, and on DGRAM IPv4 sockets outside Linux, start with the IP
+    /// header.
+    pub socket_type: Type,
+    /// The ident the reply has to carry. On Linux DGRAM sockets the kernel
+    /// replaces the ident of the request with the local port, which is what the
+    /// reply carries then.
+    pub ident: u16,
+    pub seq_cnt: u16,
+    pub payload: &'a [u8],
+}
+
+/// Which checks beyond ident and sequence number a reply has to pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStrategy {
+    /// Only match replies that echo the payload unchanged.
+    pub verify_payload: bool,
+    /// Only match replies whose ICMP checksum is correct.
+    pub verify_checksum: bool,
+}
+
+/// How the receive loop classifies a packet, see [`decide_match`].
+#[derive(Debug)]
+pub enum MatchDecision {
+    /// The reply to the request. `ttl` and `dont_fragment` are only known if the
+    /// packet started with the IP header.
+    Matched {
+        ttl: Option<u8>,
+        dont_fragment: Option<bool>,
+        checksum_valid: bool,
+    },
+    /// An echo reply with the ident of the request but another sequence number,
+    /// like a late or duplicated reply to an earlier probe of a stream. It is
+    /// ignored like a foreign packet.
+    Duplicate,
+    /// An ICMP error message about the request, like [`Error::Unreachable`].
+    IcmpError(Error),
+    /// Any other packet, e.g. a reply to another request, a reply failing the
+    /// checks of the strategy, or an error message about another request.
+    Foreign,
+    /// The IPv4 header of the packet could not be decoded, which fails the ping
+    /// with [`Error::DecodeV4Error`].
+    DecodeError,
+}
+
+/// Classifies the packet `reply`, as received from `sender`, for the echo request
+/// described by `request`, the way the receive loop of a ping does. Only
+/// available with the `testing` feature.
+pub fn decide_match(
+    request: &RequestMeta,
+    reply: &[u8],
+    sender: IpAddr,
+    strategy: &MatchStrategy,
+) -> MatchDecision {
+    let (data, ip_header) = match icmp_data(request.socket_type, request.is_ipv4, reply) {
+        Ok(decoded) => decoded,
+        Err(_) => return MatchDecision::DecodeError,
+    };
+
+    let echo_reply = if request.is_ipv4 {
+        EchoReply::decode::<IcmpV4>(data)
+    } else {
+        EchoReply::decode::<IcmpV6>(data)
+    };
+    if let Ok(echo_reply) = echo_reply {
+        if echo_reply.ident != request.ident {
+            return MatchDecision::Foreign;
+        }
+        if echo_reply.seq_cnt != request.seq_cnt {
+            return MatchDecision::Duplicate;
+        }
+        // verified payloads must come back unchanged, anything else is not our reply
+        if (strategy.verify_payload && echo_reply.payload != request.payload)
+            || (strategy.verify_checksum && !echo_reply.checksum_valid)
+        {
+            return MatchDecision::Foreign;
+        }
+        return MatchDecision::Matched {
+            ttl: ip_header.as_ref().map(|header| header.ttl),
+            dont_fragment: ip_header.as_ref().map(|header| header.dont_fragment),
+            checksum_valid: echo_reply.checksum_valid,
+        };
+    }
+
+    let message = if request.is_ipv4 {
+        ErrorMessage::decode::<IcmpV4>(data)
+    } else {
+        ErrorMessage::decode::<IcmpV6>(data)
+    };
+    let message = match message {
+        Ok(message) => message,
+        Err(_) => return MatchDecision::Foreign,
+    };
+    // only report errors whose embedded request carries our ident
+    if original_ident(request.is_ipv4, message.original) != Some(request.ident) {
+        return MatchDecision::Foreign;
+    }
+    let code = message.code;
+    let original = message.original.to_vec();
+    MatchDecision::IcmpError(match message.kind {
+        ErrorKind::DestUnreachable => Error::Unreachable {
+            code,
+            sender,
+            original,
+        },
+        ErrorKind::TimeExceeded => Error::TimeExceeded {
+            code,
+            sender,
+            original,
+        },
+        ErrorKind::PacketTooBig { mtu } => Error::PacketTooBig {
+            mtu,
+            sender,
+            original,
+        },
+    })
+}
@@ -0,0 +1,153 @@
+use std::net::IpAddr;
+
+use crate::errors::Error;
+use crate::packet::{self, EchoReply, EchoRequest, IcmpV4, IcmpV6, ICMP_HEADER_SIZE};
+use crate::socket::EchoPacket;
+
+/// The ICMP version a message is encoded for or was received with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpVersion {
+    V4,
+    V6,
+}
+
+impl IcmpVersion {
+    pub fn of(addr: IpAddr) -> Self {
+        if addr.is_ipv4() {
+            IcmpVersion::V4
+        } else {
+            IcmpVersion::V6
+        }
+    }
+}
+
+/// An ICMP message that can be sent with a [`PingSocket`](crate::PingSocket).
+///
+/// Implement this together with [`IcmpReply`] to send message types the crate
+/// does not know about. The echo request sent by [`PingSocket::send`] is the
+/// reference implementation.
+///
+/// [`PingSocket::send`]: crate::PingSocket::send
+pub trait IcmpMessage {
+    /// The message expected in response.
+    type Reply: IcmpReply;
+
+    /// The number of bytes `encode` writes.
+    fn encoded_len(&self) -> usize;
+
+    /// Writes the message to the start of `buffer`, which is `encoded_len` bytes
+    /// long, and returns the number of bytes written. The checksum has to be
+    /// filled in, e.g. with [`write_checksum`].
+    fn encode(&self, version: IcmpVersion, buffer: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// An ICMP message that can be received with a [`PingSocket`](crate::PingSocket).
+pub trait IcmpReply: Sized {
+    /// Decodes a received ICMP message, starting at its type. Fails for messages
+    /// of other types.
+    fn decode(version: IcmpVersion, buffer: &[u8]) -> Result<Self, Error>;
+}
+
+/// Computes the ICMP checksum of `buffer` and stores it in bytes 2 and 3.
+pub fn write_checksum(buffer: &mut [u8]) {
+    packet::write_checksum(buffer)
+}
+
+impl IcmpMessage for EchoRequest<'_> {
+    type Reply = EchoPacket;
+
+    fn encoded_len(&self) -> usize {
+        ICMP_HEADER_SIZE + self.payload.len()
+    }
+
+    fn encode(&self, version: IcmpVersion, buffer: &mut [u8]) -> Result<usize, Error> {
+        let encoded = match version {
+            IcmpVersion::V4 => EchoRequest::encode::<IcmpV4>(self, buffer),
+            IcmpVersion::V6 => EchoRequest::encode::<IcmpV6>(self, buffer),
+        };
+        match encoded {
+            Ok(()) => Ok(self.encoded_len()),
+            Err(_) => Err(buffer_too_small()),
+        }
+    }
+}
+
+impl IcmpReply for EchoPacket {
+    fn decode(version: IcmpVersion, buffer: &[u8]) -> Result<Self, Error> {
+        let reply = match version {
+            IcmpVersion::V4 => EchoReply::decode::<IcmpV4>(buffer),
+            IcmpVersion::V6 => EchoReply::decode::<IcmpV6>(buffer),
+        };
+        match reply {
+            Ok(reply) => Ok(EchoPacket {
+                ident: reply.ident,
+                seq_cnt: reply.seq_cnt,
+                payload: reply.payload.to_vec(),
+            }),
+            Err(_) => Err(Error::DecodeEchoReplyError),
+        }
+    }
+}
+
+/// An arbitrary ICMP message.
+///
+/// This allows sending any ICMP type, which comes with a few footguns:
+///
+/// - Linux only allows echo requests on DGRAM sockets, everything else needs a
+///   raw socket.
+/// - For ICMPv6 the kernel overwrites the checksum with its own, since it
+///   covers a pseudo header with the source address.
+/// - Nothing is matched: any received ICMP message decodes as a `RawIcmp`,
+///   including messages meant for other processes and, on loopback, copies of
+///   our own requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawIcmp {
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    /// Everything after the checksum.
+    pub body: Vec<u8>,
+}
+
+impl IcmpMessage for RawIcmp {
+    type Reply = RawIcmp;
+
+    fn encoded_len(&self) -> usize {
+        4 + self.body.len()
+    }
+
+    fn encode(&self, _version: IcmpVersion, buffer: &mut [u8]) -> Result<usize, Error> {
+        let len = self.encoded_len();
+        if buffer.len() < len {
+            return Err(buffer_too_small());
+        }
+        let buffer = &mut buffer[..len];
+        buffer[0] = self.icmp_type;
+        buffer[1] = self.icmp_code;
+        buffer[2] = 0;
+        buffer[3] = 0;
+        buffer[4..].copy_from_slice(&self.body);
+        write_checksum(buffer);
+        Ok(len)
+    }
+}
+
+impl IcmpReply for RawIcmp {
+    fn decode(_version: IcmpVersion, buffer: &[u8]) -> Result<Self, Error> {
+        if buffer.len() < 4 {
+            return Err(Error::DecodeIcmpError);
+        }
+        Ok(RawIcmp {
+            icmp_type: buffer[0],
+            icmp_code: buffer[1],
+            body: buffer[4..].to_vec(),
+        })
+    }
+}
+
+fn buffer_too_small() -> Error {
+    let error = std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "buffer too small for the message",
+    );
+    Error::IoError { error }
+}
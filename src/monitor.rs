@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use socket2::Socket;
+
+use crate::cancel::{self, CancelToken};
+use crate::errors::Error;
+use crate::ping::{exchange, open_socket, Ping, PingReply};
+use crate::stats::{HealthStatus, HealthThresholds, PingStatistics};
+
+const DEFAULT_WINDOW: usize = 20;
+
+/// How a [`Monitor`] pings one target.
+#[derive(Debug, Clone)]
+pub struct MonitorTarget {
+    /// The ping sent every round, which also gives the target address.
+    pub ping: Ping<'static>,
+    /// Time between the starts of two rounds.
+    pub interval: Duration,
+    /// Number of most recent probes the health is computed over. Defaults to 20.
+    pub window: usize,
+    /// Thresholds the health is classified with.
+    pub thresholds: HealthThresholds,
+}
+
+impl MonitorTarget {
+    pub fn new(ping: Ping<'static>, interval: Duration) -> Self {
+        MonitorTarget {
+            ping,
+            interval,
+            window: DEFAULT_WINDOW,
+            thresholds: HealthThresholds::default(),
+        }
+    }
+}
+
+/// The current health of a target of a [`Monitor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetHealth {
+    /// The health over the window, `None` until the first probe finished.
+    pub status: Option<HealthStatus>,
+    /// The RTT of the latest reply in the window.
+    pub last_rtt: Option<Duration>,
+    /// Fraction of the probes in the window without reply, between 0 and 1.
+    pub loss: f64,
+    /// Number of probes in the window.
+    pub probes: usize,
+}
+
+#[derive(Debug)]
+struct Window {
+    results: VecDeque<Result<PingReply, Error>>,
+    size: usize,
+    thresholds: HealthThresholds,
+}
+
+impl Window {
+    fn push(&mut self, result: Result<PingReply, Error>) {
+        if self.results.len() >= self.size {
+            self.results.pop_front();
+        }
+        self.results.push_back(result);
+    }
+
+    fn health(&mut self) -> TargetHealth {
+        let results = self.results.make_contiguous();
+        let statistics = PingStatistics::from_results_with(results, None);
+        TargetHealth {
+            status: if results.is_empty() {
+                None
+            } else {
+                Some(statistics.health_with(&self.thresholds))
+            },
+            last_rtt: results
+                .iter()
+                .rev()
+                .find_map(|result| result.as_ref().ok())
+                .map(|reply| reply.rtt),
+            loss: statistics.loss,
+            probes: results.len(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Worker {
+    window: Arc<Mutex<Window>>,
+    cancel: CancelToken,
+    handle: JoinHandle<()>,
+}
+
+/// Pings a changing set of targets, each on its own schedule, and keeps a rolling
+/// window of their results, e.g. for a dashboard.
+///
+/// Every target is pinged by its own thread, which opens one socket with all the
+/// options of the target's ping and keeps it for all of its rounds, so a target
+/// costs a thread and a file descriptor. A socket that fails to open is retried
+/// the next round. Sequence numbers count up from round to round unless the ping
+/// has a fixed one. A round that takes longer than the interval delays the
+/// following ones.
+///
+/// Removing a target, or dropping the monitor, cancels its thread, including a
+/// ping waiting for its reply, and joins it, which takes up to 50 ms.
+#[derive(Debug)]
+pub struct Monitor {
+    workers: HashMap<IpAddr, Worker>,
+}
+
+impl Monitor {
+    /// Starts pinging `targets`. A later target with the same address as an
+    /// earlier one replaces it.
+    pub fn new(targets: Vec<MonitorTarget>) -> Self {
+        let mut monitor = Monitor {
+            workers: HashMap::new(),
+        };
+        for target in targets {
+            monitor.add(target);
+        }
+        monitor
+    }
+
+    /// Starts pinging `target`, replacing and restarting a target with the same
+    /// address.
+    pub fn add(&mut self, target: MonitorTarget) {
+        let addr = target.ping.addr;
+        self.remove(addr);
+
+        let window = Arc::new(Mutex::new(Window {
+            results: VecDeque::with_capacity(target.window),
+            size: target.window.max(1),
+            thresholds: target.thresholds,
+        }));
+        let cancel = CancelToken::new();
+        let handle = {
+            let window = Arc::clone(&window);
+            let cancel = cancel.clone();
+            thread::spawn(move || run(target, &window, &cancel))
+        };
+        self.workers.insert(
+            addr,
+            Worker {
+                window,
+                cancel,
+                handle,
+            },
+        );
+    }
+
+    /// Stops pinging `addr` and forgets its results. Returns whether it was a
+    /// target.
+    pub fn remove(&mut self, addr: IpAddr) -> bool {
+        match self.workers.remove(&addr) {
+            Some(worker) => {
+                worker.cancel.cancel();
+                let _ = worker.handle.join();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The current health of every target.
+    pub fn snapshot(&self) -> HashMap<IpAddr, TargetHealth> {
+        self.workers
+            .iter()
+            .filter_map(|(addr, worker)| {
+                let mut window = worker.window.lock().ok()?;
+                Some((*addr, window.health()))
+            })
+            .collect()
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        for worker in self.workers.values() {
+            worker.cancel.cancel();
+        }
+        for (_, worker) in self.workers.drain() {
+            let _ = worker.handle.join();
+        }
+    }
+}
+
+/// Pings the target every interval until cancelled.
+fn run(target: MonitorTarget, window: &Mutex<Window>, cancel: &CancelToken) {
+    let ping = target.ping.cancel_token(cancel.clone());
+    let mut socket = None;
+    let mut seq_cnt = 1u16;
+    let mut next_send = Instant::now();
+    loop {
+        loop {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let now = Instant::now();
+            if next_send <= now {
+                break;
+            }
+            thread::sleep((next_send - now).min(cancel::POLL_INTERVAL));
+        }
+        next_send += target.interval;
+
+        let round = match ping.seq_cnt {
+            Some(_) => ping.clone(),
+            None => ping.clone().seq_cnt(seq_cnt),
+        };
+        seq_cnt = seq_cnt.wrapping_add(1);
+        let result = ping_on(&round, &mut socket);
+        if let Err(Error::Cancelled) = result {
+            return;
+        }
+        if let Ok(mut window) = window.lock() {
+            window.push(result);
+        }
+    }
+}
+
+/// Pings on `socket`, opening it first if it is not open yet.
+fn ping_on(ping: &Ping, socket: &mut Option<Socket>) -> Result<PingReply, Error> {
+    if socket.is_none() {
+        *socket = Some(open_socket(ping)?);
+    }
+    match socket {
+        Some(socket) => crate::otel::in_span(ping.addr, || exchange(ping, socket)),
+        None => Err(Error::InternalError),
+    }
+}
@@ -0,0 +1,89 @@
+use std::net::IpAddr;
+
+use crate::errors::Error;
+use crate::ping::PingReply;
+
+/// The trace context a ping should be recorded under, captured on the thread that
+/// requested the ping.
+#[derive(Debug, Clone)]
+pub(crate) struct Parent {
+    #[cfg(feature = "otel")]
+    context: opentelemetry::Context,
+}
+
+impl Parent {
+    pub(crate) fn capture() -> Self {
+        Parent {
+            #[cfg(feature = "otel")]
+            context: opentelemetry::Context::current(),
+        }
+    }
+
+    pub(crate) fn in_scope<T>(self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "otel")]
+        let _guard = self.context.attach();
+        f()
+    }
+}
+
+/// Runs `f` inside a `ping` span that is a child of the current context.
+#[cfg(feature = "otel")]
+pub(crate) fn in_span(
+    addr: IpAddr,
+    f: impl FnOnce() -> Result<PingReply, Error>,
+) -> Result<PingReply, Error> {
+    use opentelemetry::trace::{Span, Status, Tracer};
+    use opentelemetry::{global, Context, KeyValue};
+
+    let tracer = global::tracer("ping");
+    let mut span = tracer.start_with_context("ping", &Context::current());
+    span.set_attribute(KeyValue::new("net.peer.ip", addr.to_string()));
+
+    let result = f();
+    match &result {
+        Ok(reply) => {
+            span.set_attribute(KeyValue::new(
+                "ping.rtt_ms",
+                reply.rtt.as_secs_f64() * 1000.0,
+            ));
+            span.set_attribute(KeyValue::new("ping.seq", i64::from(reply.seq_cnt)));
+            span.set_status(Status::Ok);
+        }
+        Err(error) => span.set_status(Status::error(error.to_string())),
+    }
+    span.end();
+    result
+}
+
+/// Runs `f` inside a `ping_batch` span that is a child of the current context and
+/// current while `f` runs, so pings capturing it become its children.
+#[cfg(feature = "otel")]
+pub(crate) fn in_batch_span<T>(targets: usize, f: impl FnOnce() -> T) -> T {
+    use opentelemetry::trace::{Span, TraceContextExt, Tracer};
+    use opentelemetry::{global, Context, KeyValue};
+
+    let tracer = global::tracer("ping");
+    let mut span = tracer.start_with_context("ping_batch", &Context::current());
+    span.set_attribute(KeyValue::new("ping.batch.targets", targets as i64));
+
+    let context = Context::current_with_span(span);
+    let result = {
+        let _guard = context.clone().attach();
+        f()
+    };
+    context.span().end();
+    result
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn in_batch_span<T>(_targets: usize, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn in_span(
+    _addr: IpAddr,
+    f: impl FnOnce() -> Result<PingReply, Error>,
+) -> Result<PingReply, Error> {
+    f()
+}
@@ -19,6 +19,12 @@ pub trait Proto {
     const ECHO_REQUEST_CODE: u8;
     const ECHO_REPLY_TYPE: u8;
     const ECHO_REPLY_CODE: u8;
+    const DEST_UNREACHABLE_TYPE: u8;
+    const TIME_EXCEEDED_TYPE: u8;
+    /// ICMPv4 reports too big packets as destination unreachable instead.
+    const PACKET_TOO_BIG_TYPE: Option<u8>;
+    /// ICMPv6 checksums cover a pseudo header with the IP addresses as well.
+    const CHECKSUM_PSEUDO_HEADER: bool;
 }
 
 impl Proto for IcmpV4 {
@@ -26,6 +32,10 @@ impl Proto for IcmpV4 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 0;
     const ECHO_REPLY_CODE: u8 = 0;
+    const DEST_UNREACHABLE_TYPE: u8 = 3;
+    const TIME_EXCEEDED_TYPE: u8 = 11;
+    const PACKET_TOO_BIG_TYPE: Option<u8> = None;
+    const CHECKSUM_PSEUDO_HEADER: bool = false;
 }
 
 impl Proto for IcmpV6 {
@@ -33,6 +43,10 @@ impl Proto for IcmpV6 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 129;
     const ECHO_REPLY_CODE: u8 = 0;
+    const DEST_UNREACHABLE_TYPE: u8 = 1;
+    const TIME_EXCEEDED_TYPE: u8 = 3;
+    const PACKET_TOO_BIG_TYPE: Option<u8> = Some(2);
+    const CHECKSUM_PSEUDO_HEADER: bool = true;
 }
 
 pub struct EchoRequest<'a> {
@@ -62,10 +76,13 @@ impl EchoRequest<'_> {
 
 pub struct EchoReply<'a> {
     pub ident: u16,
-    #[allow(dead_code)]
     pub seq_cnt: u16,
     #[allow(dead_code)]
     pub payload: &'a [u8],
+    /// Whether the checksum matched the message as received. ICMPv6 checksums
+    /// cover the IP addresses, which are not available here, and the kernel
+    /// already drops ICMPv6 messages with bad checksums, so they count as valid.
+    pub checksum_valid: bool,
 }
 
 impl<'a> EchoReply<'a> {
@@ -89,11 +106,79 @@ impl<'a> EchoReply<'a> {
             ident,
             seq_cnt,
             payload,
+            checksum_valid: P::CHECKSUM_PSEUDO_HEADER || checksum(buffer) == 0,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    DestUnreachable,
+    TimeExceeded,
+    PacketTooBig { mtu: u32 },
+}
+
+pub struct ErrorMessage<'a> {
+    pub kind: ErrorKind,
+    pub code: u8,
+    pub original: &'a [u8],
+}
+
+impl<'a> ErrorMessage<'a> {
+    pub fn decode<P: Proto>(buffer: &'a [u8]) -> Result<Self, Error> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(Error::InvalidSize);
+        }
+
+        let kind = if buffer[0] == P::DEST_UNREACHABLE_TYPE && P::PACKET_TOO_BIG_TYPE.is_none() {
+            // code 4 is fragmentation needed, with the next hop MTU in bytes 6 and 7
+            if buffer[1] == 4 {
+                ErrorKind::PacketTooBig {
+                    mtu: u32::from(u16::from_be_bytes([buffer[6], buffer[7]])),
+                }
+            } else {
+                ErrorKind::DestUnreachable
+            }
+        } else if buffer[0] == P::DEST_UNREACHABLE_TYPE {
+            ErrorKind::DestUnreachable
+        } else if Some(buffer[0]) == P::PACKET_TOO_BIG_TYPE {
+            ErrorKind::PacketTooBig {
+                mtu: u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]),
+            }
+        } else if buffer[0] == P::TIME_EXCEEDED_TYPE {
+            ErrorKind::TimeExceeded
+        } else {
+            return Err(Error::InvalidPacket);
+        };
+
+        Ok(ErrorMessage {
+            kind,
+            code: buffer[1],
+            original: &buffer[HEADER_SIZE..],
         })
     }
 }
 
-fn write_checksum(buffer: &mut [u8]) {
+/// Returns the ident of the echo request at the start of `buffer`, if any.
+pub fn echo_request_ident<P: Proto>(buffer: &[u8]) -> Option<u16> {
+    if buffer.len() < HEADER_SIZE || buffer[0] != P::ECHO_REQUEST_TYPE {
+        return None;
+    }
+    Some((u16::from(buffer[4]) << 8) + u16::from(buffer[5]))
+}
+
+pub fn write_checksum(buffer: &mut [u8]) {
+    buffer[2] = 0;
+    buffer[3] = 0;
+    let sum = checksum(buffer);
+
+    buffer[2] = (sum >> 8) as u8;
+    buffer[3] = (sum & 0xff) as u8;
+}
+
+/// Computes the internet checksum of `buffer`, which is zero for a message that
+/// already carries a correct checksum.
+fn checksum(buffer: &[u8]) -> u16 {
     let mut sum = 0u32;
     for word in buffer.chunks(2) {
         let mut part = u16::from(word[0]) << 8;
@@ -107,8 +192,5 @@ fn write_checksum(buffer: &mut [u8]) {
         sum = (sum & 0xffff) + (sum >> 16);
     }
 
-    let sum = !sum as u16;
-
-    buffer[2] = (sum >> 8) as u8;
-    buffer[3] = (sum & 0xff) as u8;
+    !sum as u16
 }
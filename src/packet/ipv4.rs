@@ -31,6 +31,8 @@ impl IpV4Protocol {
 pub struct IpV4Packet<'a> {
     #[allow(dead_code)]
     pub protocol: IpV4Protocol,
+    pub ttl: u8,
+    pub dont_fragment: bool,
     pub data: &'a [u8],
 }
 
@@ -58,6 +60,8 @@ impl<'a> IpV4Packet<'a> {
 
         Ok(Self {
             protocol,
+            ttl: data[8],
+            dont_fragment: data[6] & 0x40 != 0,
             data: &data[header_size..],
         })
     }
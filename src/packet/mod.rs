@@ -1,6 +1,9 @@
 mod icmp;
 mod ipv4;
 
-pub use self::icmp::{EchoReply, EchoRequest, IcmpV4, IcmpV6, HEADER_SIZE as ICMP_HEADER_SIZE};
+pub use self::icmp::{
+    echo_request_ident, write_checksum, EchoReply, EchoRequest, ErrorKind, ErrorMessage, IcmpV4,
+    IcmpV6, HEADER_SIZE as ICMP_HEADER_SIZE,
+};
 
 pub use self::ipv4::IpV4Packet;
@@ -0,0 +1,277 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use socket2::Type;
+
+use crate::errors::Error;
+use crate::ping::Ping;
+use crate::stats::PingStatistics;
+
+const MAX_HOPS: u32 = 30;
+
+/// Hop counts of the forward and return path to a host, see [`path_symmetry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathSymmetry {
+    /// The smallest TTL with which our echo requests reach the host.
+    pub forward_hops: u8,
+    /// The estimated number of hops the echo replies took back to us.
+    pub return_hops: u8,
+    /// Whether the hop counts differ by more than one hop.
+    pub asymmetric: bool,
+}
+
+/// Compares the number of hops to `addr` with the number of hops back.
+///
+/// The forward hop count is found like traceroute does, by pinging with TTL 1, 2,
+/// ... until a reply arrives. The return hop count is a heuristic: the host's
+/// initial TTL is assumed to be the smallest of 64, 128 and 255 that is not below
+/// the TTL the reply arrived with, and the difference between the two is taken as
+/// the number of hops. Hosts using other initial TTLs or middleboxes rewriting the
+/// TTL make the estimate wrong.
+///
+/// This needs a raw socket since the reply's TTL is read from its IP header, and
+/// it only supports IPv4 targets.
+pub fn path_symmetry(addr: IpAddr, timeout: Duration) -> Result<PathSymmetry, Error> {
+    if !addr.is_ipv4() {
+        return Err(Error::InvalidProtocol);
+    }
+
+    for ttl in 1..=MAX_HOPS {
+        let result = Ping::new(addr)
+            .socket_type(Type::RAW)
+            .timeout(timeout)
+            .ttl(ttl)
+            .ping();
+        let reply = match result {
+            Ok(reply) => reply,
+            Err(error) if error.is_transient() => continue,
+            Err(error) => return Err(error),
+        };
+
+        let received_ttl = match reply.ttl {
+            Some(received_ttl) => received_ttl,
+            None => return Err(Error::InternalError),
+        };
+        let initial_ttl: u8 = match received_ttl {
+            0..=64 => 64,
+            65..=128 => 128,
+            _ => 255,
+        };
+        let forward_hops = ttl as u8;
+        // a host one hop away receives our request with TTL 1 and its reply
+        // arrives with its initial TTL minus the routers in between
+        let return_hops = initial_ttl - received_ttl + 1;
+        return Ok(PathSymmetry {
+            forward_hops,
+            return_hops,
+            asymmetric: forward_hops.abs_diff(return_hops) > 1,
+        });
+    }
+
+    let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "Host not reached");
+    Err(Error::IoError { error })
+}
+
+/// The first router on the path to a target, see [`first_hop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirstHop {
+    pub addr: IpAddr,
+    /// Time from sending the probe until the router's reply arrived.
+    pub rtt: Duration,
+}
+
+/// Finds the first router on the path to `addr` by pinging it with TTL 1, which
+/// the router answers with a time exceeded message. If `addr` is directly
+/// reachable it answers the ping itself and is returned as the first hop.
+///
+/// This needs a raw socket to receive the time exceeded message.
+pub fn first_hop(addr: IpAddr, timeout: Duration) -> Result<FirstHop, Error> {
+    let (result, events) = Ping::new(addr)
+        .socket_type(Type::RAW)
+        .timeout(timeout)
+        .ttl(1)
+        .ping_collecting(false);
+    match result {
+        Ok(reply) => Ok(FirstHop {
+            addr,
+            rtt: reply.rtt,
+        }),
+        Err(Error::TimeExceeded { sender, .. }) => {
+            // the message that ended the ping was collected last
+            let event = events.last().ok_or(Error::InternalError)?;
+            Ok(FirstHop {
+                addr: sender,
+                rtt: event.elapsed,
+            })
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// IPv4 and ICMP header of the small comparison probe plus the 56 byte payload
+/// classic `ping` sends.
+const SMALL_PROBE_SIZE: usize = 84;
+const IPV4_HEADER_SIZE: usize = 20;
+const IPV6_HEADER_SIZE: usize = 40;
+const ICMP_HEADER_SIZE: usize = 8;
+
+/// The outcome of [`detect_pmtu_blackhole`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmtuVerdict {
+    /// The large probe was answered, packets of the probed size get through.
+    Passes,
+    /// The large probe was rejected with a packet too big error, so path MTU
+    /// discovery works. `mtu` is the MTU of the link that rejected it, or `None` if
+    /// the local host refused to send the probe because it already knows a smaller
+    /// path MTU.
+    TooBig { mtu: Option<u32> },
+    /// The large probe was silently dropped while the small probe was answered.
+    Blackhole,
+    /// Neither probe was answered, so nothing can be said about the path MTU.
+    Unreachable,
+}
+
+/// Checks whether packets of `size` bytes, counting the IP header, are silently
+/// dropped on the way to `addr`.
+///
+/// An echo request of `size` bytes with the don't fragment bit set is sent. If it
+/// is lost, a request of 84 bytes is sent as well, and if only that one is
+/// answered the path is reported as a [`PmtuVerdict::Blackhole`]: a link with a
+/// smaller MTU drops large packets without the packet too big error path MTU
+/// discovery relies on, typically because a firewall filters ICMP.
+///
+/// This is a heuristic. A single lost probe, rate limiting of echo requests or a
+/// host that does not answer large requests all look like a blackhole, so repeat
+/// the check before acting on it. Only the path to `addr` is probed, not the path
+/// back. Setting the don't fragment bit is only supported on Linux.
+///
+/// This needs a raw socket to receive the packet too big errors.
+pub fn detect_pmtu_blackhole(
+    addr: IpAddr,
+    size: usize,
+    timeout: Duration,
+) -> Result<PmtuVerdict, Error> {
+    let ip_header_size = if addr.is_ipv4() {
+        IPV4_HEADER_SIZE
+    } else {
+        IPV6_HEADER_SIZE
+    };
+    let probe = |size: usize| {
+        Ping::new(addr)
+            .socket_type(Type::RAW)
+            .timeout(timeout)
+            .payload_size(size.saturating_sub(ip_header_size + ICMP_HEADER_SIZE))
+            .dont_fragment(true)
+            .ping()
+    };
+
+    match probe(size) {
+        Ok(_) => return Ok(PmtuVerdict::Passes),
+        Err(Error::PacketTooBig { mtu, .. }) => return Ok(PmtuVerdict::TooBig { mtu: Some(mtu) }),
+        Err(Error::IoError { error }) if is_message_too_long(&error) => {
+            return Ok(PmtuVerdict::TooBig { mtu: None })
+        }
+        Err(error) if error.is_transient() => {}
+        Err(error) => return Err(error),
+    }
+
+    match probe(SMALL_PROBE_SIZE) {
+        Ok(_) => Ok(PmtuVerdict::Blackhole),
+        Err(error) if error.is_transient() => Ok(PmtuVerdict::Unreachable),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(unix)]
+fn is_message_too_long(error: &std::io::Error) -> bool {
+    error.raw_os_error() == Some(libc::EMSGSIZE)
+}
+
+#[cfg(not(unix))]
+fn is_message_too_long(_error: &std::io::Error) -> bool {
+    false
+}
+
+/// Settings of [`find_loss_threshold_with`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LossThresholdSearch {
+    /// Payload size of the first probes, in bytes. Defaults to 64.
+    pub min_payload_size: usize,
+    /// Largest payload size probed. Defaults to 1472, which fills a 1500 byte
+    /// IPv4 packet.
+    pub max_payload_size: usize,
+    /// Bytes added to the payload size from one step to the next. Defaults to 64.
+    pub step: usize,
+    /// Probes sent per payload size. More repeats make it less likely that a
+    /// single lost probe on a healthy path is taken for the threshold, at the cost
+    /// of a longer search. Defaults to 5.
+    pub repeats: usize,
+    /// Loss fraction a payload size has to exceed to count as lossy. Defaults to
+    /// 0.2, so with 5 repeats at least 2 probes have to be lost.
+    pub max_loss: f64,
+    /// Time to wait for each reply. Defaults to 1 second.
+    pub timeout: Duration,
+}
+
+impl Default for LossThresholdSearch {
+    fn default() -> Self {
+        LossThresholdSearch {
+            min_payload_size: 64,
+            max_payload_size: 1472,
+            step: 64,
+            repeats: 5,
+            max_loss: 0.2,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The smallest lossy payload size found by [`find_loss_threshold_with`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LossThreshold {
+    pub payload_size: usize,
+    /// Statistics of the probes of this size.
+    pub statistics: PingStatistics,
+}
+
+/// Finds the payload size at which the path to `addr` starts losing packets,
+/// using the default [`LossThresholdSearch`].
+pub fn find_loss_threshold(addr: IpAddr) -> Result<Option<LossThreshold>, Error> {
+    find_loss_threshold_with(addr, &LossThresholdSearch::default())
+}
+
+/// Finds the payload size at which the path to `addr` starts losing packets.
+///
+/// Starting at `min_payload_size`, `repeats` probes are sent per size, one after
+/// the other, and the size grows by `step` until the loss of a size exceeds
+/// `max_loss` or the next step would exceed `max_payload_size`. The first lossy
+/// size is returned, or `None` if every size got through. Unlike
+/// [`detect_pmtu_blackhole`] the probes may be fragmented, so this finds sizes
+/// that are dropped for other reasons, like a link or queue that loses large
+/// packets. Loss that is unrelated to the size makes the threshold come out too
+/// small; repeat the search or raise `repeats` to rule that out.
+pub fn find_loss_threshold_with(
+    addr: IpAddr,
+    search: &LossThresholdSearch,
+) -> Result<Option<LossThreshold>, Error> {
+    let sizes = (search.min_payload_size..=search.max_payload_size).step_by(search.step.max(1));
+    for payload_size in sizes {
+        let burst = Ping::new(addr)
+            .timeout(search.timeout)
+            .payload_size(payload_size)
+            .burst(search.repeats, Duration::ZERO);
+        // the burst stops at the first error that is not transient
+        if let Some(Err(error)) = burst.results.into_iter().last() {
+            if !error.is_transient() {
+                return Err(error);
+            }
+        }
+        if burst.statistics.loss > search.max_loss {
+            return Ok(Some(LossThreshold {
+                payload_size,
+                statistics: burst.statistics,
+            }));
+        }
+    }
+    Ok(None)
+}
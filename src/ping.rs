@@ -1,16 +1,131 @@
-use std::io::Read;
-use std::net::{IpAddr, SocketAddr};
-use std::time::{Duration, SystemTime};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use rand::random;
+use rand::{Rng, RngCore};
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 
+use crate::cancel::{self, CancelToken};
+use crate::clock::clock_resolution;
+use crate::counters::{socket_drops, DropCounters};
+use crate::dns::{self, FcrdnsReply};
 use crate::errors::Error;
-use crate::packet::{EchoReply, EchoRequest, IcmpV4, IcmpV6, IpV4Packet, ICMP_HEADER_SIZE};
+use crate::ident::InFlight;
+use crate::matching::{decide_match, MatchDecision, MatchStrategy, RequestMeta};
+use crate::packet::{
+    echo_request_ident, EchoRequest, IcmpV4, IcmpV6, IpV4Packet, ICMP_HEADER_SIZE,
+};
+use crate::record::{EventKind, PingEvent};
+use crate::stats::{Burst, ConfidenceTarget, HealthThresholds, PingStatistics, RttAccumulator};
+use crate::stream::PingStream;
 
-const TOKEN_SIZE: usize = 24;
-const ECHO_REQUEST_BUFFER_SIZE: usize = ICMP_HEADER_SIZE + TOKEN_SIZE;
-type Token = [u8; TOKEN_SIZE];
+pub(crate) const TOKEN_SIZE: usize = 24;
+const IPV6_HEADER_SIZE: usize = 40;
+/// Shortest time the receive loop waits for a packet at once.
+const MIN_WAIT: Duration = Duration::from_millis(1);
+pub(crate) type Token = [u8; TOKEN_SIZE];
+
+/// The order in which a new socket is bound to the interface and to the source
+/// address, see [`Ping::bind_order`]. The socket is connected after both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BindOrder {
+    /// Binds to the interface first, so binding to the address already happens
+    /// with the interface restriction in place. This is the default.
+    #[default]
+    DeviceFirst,
+    /// Binds to the address first and restricts the socket to the interface
+    /// afterwards.
+    AddressFirst,
+}
+
+/// An ICMP error message about our echo request, like a redirect or a time
+/// exceeded message, see [`Ping::ping_verbose`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcmpEvent {
+    pub sender: IpAddr,
+    pub icmp_type: u8,
+    pub icmp_code: u8,
+    /// Time from sending the request to receiving the message.
+    pub elapsed: Duration,
+    /// The copy of our echo request embedded in the message, starting at its IP
+    /// header.
+    pub original: Vec<u8>,
+}
+
+impl IcmpEvent {
+    /// ICMPv4 destination unreachable, source quench, redirect, time exceeded and
+    /// parameter problem messages.
+    const V4_ERROR_TYPES: [u8; 5] = [3, 4, 5, 11, 12];
+    /// ICMPv6 destination unreachable, packet too big, time exceeded and parameter
+    /// problem messages.
+    const V6_ERROR_TYPES: [u8; 4] = [1, 2, 3, 4];
+
+    /// Decodes `data` if it is an ICMP error message embedding the request with
+    /// `ident`.
+    fn decode(
+        is_ipv4: bool,
+        data: &[u8],
+        sender: IpAddr,
+        send_time: Instant,
+        ident: u16,
+    ) -> Option<Self> {
+        let icmp_type = *data.first()?;
+        let is_error = if is_ipv4 {
+            Self::V4_ERROR_TYPES.contains(&icmp_type)
+        } else {
+            Self::V6_ERROR_TYPES.contains(&icmp_type)
+        };
+        let original = data.get(ICMP_HEADER_SIZE..)?;
+        if !is_error || original_ident(is_ipv4, original) != Some(ident) {
+            return None;
+        }
+        Some(IcmpEvent {
+            sender,
+            icmp_type,
+            icmp_code: data[1],
+            elapsed: send_time.elapsed(),
+            original: original.to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingReply {
+    pub ident: u16,
+    pub seq_cnt: u16,
+    /// The round trip time, which is never zero: an RTT below the
+    /// [`clock_resolution`](crate::clock_resolution) is reported as the resolution
+    /// unless [`Ping::rtt_resolution`] rounds it further.
+    pub rtt: Duration,
+    /// The TTL the reply arrived with. Only available on raw IPv4 sockets, where
+    /// the IP header is received along with the ICMP message.
+    pub ttl: Option<u8>,
+    /// Whether the reply arrived with the don't fragment bit set. Only available
+    /// on raw IPv4 sockets, like `ttl`.
+    pub dont_fragment: Option<bool>,
+    /// Whether the reply's ICMP checksum was correct as received, e.g. to detect
+    /// middleboxes that rewrite replies without fixing the checksum. Replies with
+    /// a bad checksum are still accepted unless
+    /// [`verify_checksum`](Ping::verify_checksum) is set. Always `true` for
+    /// ICMPv6, whose replies with bad checksums are dropped by the kernel.
+    pub checksum_valid: bool,
+    /// The wall clock time the request was sent at, to line replies up with
+    /// packet captures. Only set with [`send_wallclock`](Ping::send_wallclock).
+    /// It is read right before sending, while `rtt` is measured on the monotonic
+    /// clock, so a wall clock adjustment during the ping does not affect the RTT.
+    pub send_wallclock: Option<SystemTime>,
+    /// When the reply was received, on the monotonic clock the RTT is measured on.
+    pub received_at: Instant,
+    /// Time spent before sending: opening and configuring the socket, or only
+    /// configuring it when pinging on a reused [`PingSocket`](crate::PingSocket).
+    /// Not part of `rtt`. Only set with [`measure_setup`](Ping::measure_setup).
+    pub setup_time: Option<Duration>,
+}
 
 #[allow(clippy::too_many_arguments)]
 fn ping_with_socktype(
@@ -23,7 +138,244 @@ fn ping_with_socktype(
     ident: Option<u16>,
     seq_cnt: Option<u16>,
     payload: Option<&Token>,
-) -> Result<(), Error> {
+) -> Result<PingReply, Error> {
+    Ping {
+        socket_type,
+        addr,
+        iface,
+        bind,
+        timeout,
+        ttl,
+        ident,
+        seq_cnt,
+        payload,
+        ..Ping::new(addr)
+    }
+    .ping()
+}
+
+fn send_echo_request(ping: &Ping) -> Result<PingReply, Error> {
+    let setup_start = setup_start(ping);
+    let socket = match open_socket(ping) {
+        Ok(socket) => socket,
+        Err(error) => {
+            let result = Err(error);
+            ping.emit(EventKind::from_result(&result));
+            return result;
+        }
+    };
+    exchange_with(ping, &socket, setup_start, None)
+}
+
+/// Starts timing the setup for [`PingReply::setup_time`], if asked to.
+fn setup_start(ping: &Ping) -> Option<Instant> {
+    if ping.measure_setup {
+        Some(Instant::now())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn open_socket(ping: &Ping) -> Result<Socket, Error> {
+    let socket = if ping.addr.is_ipv4() {
+        Socket::new(Domain::IPV4, ping.socket_type, Some(Protocol::ICMPV4))?
+    } else {
+        Socket::new(Domain::IPV6, ping.socket_type, Some(Protocol::ICMPV6))?
+    };
+
+    let device = match ping.vrf {
+        Some(_) if ping.iface.is_some() => {
+            let error = std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "a socket can be bound to either a VRF or an interface",
+            );
+            return Err(Error::IoError { error });
+        }
+        Some(vrf) => {
+            check_vrf(vrf)?;
+            Some(vrf.as_bytes())
+        }
+        None => ping.iface,
+    };
+    let bind = |socket: &Socket| match (&ping.source_ports, ping.socket_type) {
+        (Some(ports), Type::DGRAM) => bind_port_in(socket, ping, ports),
+        _ => match ping.bind {
+            Some(bind) => socket.bind(&SockAddr::from(SocketAddr::new(bind, 0))),
+            None => Ok(()),
+        },
+    };
+    match ping.bind_order {
+        BindOrder::DeviceFirst => {
+            socket.bind_device(device)?;
+            bind(&socket)?;
+        }
+        BindOrder::AddressFirst => {
+            bind(&socket)?;
+            socket.bind_device(device)?;
+        }
+    }
+
+    Ok(socket)
+}
+
+/// Binds `socket` to the bind address of `ping`, or the unspecified address, and
+/// a port from `ports`, starting at a random one and moving on to the next while
+/// they are taken.
+fn bind_port_in(socket: &Socket, ping: &Ping, ports: &RangeInclusive<u16>) -> std::io::Result<()> {
+    let addr = match ping.bind {
+        Some(bind) => bind,
+        None if ping.addr.is_ipv4() => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        None => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let (start, end) = (*ports.start(), *ports.end());
+    if start > end {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "empty source port range",
+        ));
+    }
+    // Linux enables address reuse on ICMP sockets, which lets them share a port
+    socket.set_reuse_address(false)?;
+    let size = u32::from(end - start) + 1;
+    let offset = rand::thread_rng().gen_range(0..size);
+    let mut result = Ok(());
+    for attempt in 0..size {
+        let port = start + ((offset + attempt) % size) as u16;
+        result = socket.bind(&SockAddr::from(SocketAddr::new(addr, port)));
+        match &result {
+            Err(error) if error.kind() == std::io::ErrorKind::AddrInUse => continue,
+            _ => return result,
+        }
+    }
+    result
+}
+
+/// Fails unless `name` is a VRF device.
+#[cfg(target_os = "linux")]
+fn check_vrf(name: &str) -> Result<(), Error> {
+    let uevent = std::path::Path::new("/sys/class/net")
+        .join(name)
+        .join("uevent");
+    let uevent = match std::fs::read_to_string(uevent) {
+        Ok(uevent) => uevent,
+        Err(_) => {
+            let error = std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no network device named {}", name),
+            );
+            return Err(Error::IoError { error });
+        }
+    };
+    if !uevent.lines().any(|line| line == "DEVTYPE=vrf") {
+        let error = std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("network device {} is not a VRF", name),
+        );
+        return Err(Error::IoError { error });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn check_vrf(_name: &str) -> Result<(), Error> {
+    let error = std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "VRFs are only supported on Linux",
+    );
+    Err(Error::IoError { error })
+}
+
+/// Sends the echo request described by `ping` on `socket` and waits for its reply.
+pub(crate) fn exchange(ping: &Ping, socket: &Socket) -> Result<PingReply, Error> {
+    exchange_with(ping, socket, setup_start(ping), None)
+}
+
+/// What [`Ping::ping_verbose`] collects while waiting for the reply.
+#[derive(Default)]
+struct Verbose {
+    events: Vec<IcmpEvent>,
+    /// Whether to keep waiting for the reply after an ICMP error about our request
+    /// instead of returning the error.
+    keep_waiting: bool,
+    /// The latest ICMP error about our request, returned if no reply follows.
+    last_error: Option<Error>,
+}
+
+/// Sends the request and waits for its reply, timing the setup from
+/// `setup_start` on if given.
+fn exchange_with(
+    ping: &Ping,
+    socket: &Socket,
+    setup_start: Option<Instant>,
+    mut verbose: Option<&mut Verbose>,
+) -> Result<PingReply, Error> {
+    let drops = || socket_drops(socket, ping.addr.is_ipv4(), ping.socket_type);
+    // a socket only shows up in the tables once bound, before it dropped nothing
+    let drops_before = ping.socket_drops.as_ref().map(|_| drops().unwrap_or(0));
+    let result = match exchange_inner(ping, socket, setup_start, verbose.as_deref_mut()) {
+        Err(error) if error.is_timeout() => {
+            match verbose.and_then(|verbose| verbose.last_error.take()) {
+                Some(last_error) => Err(last_error),
+                None => Err(error),
+            }
+        }
+        result => result,
+    };
+    if let (Some(total), Some(before)) = (&ping.socket_drops, drops_before) {
+        let after = drops().unwrap_or(before);
+        total.fetch_add(after.saturating_sub(before), Ordering::Relaxed);
+    }
+    ping.emit(EventKind::from_result(&result));
+    result
+}
+
+fn exchange_inner(
+    ping: &Ping,
+    socket: &Socket,
+    setup_start: Option<Instant>,
+    mut verbose: Option<&mut Verbose>,
+) -> Result<PingReply, Error> {
+    let Ping {
+        socket_type,
+        addr,
+        iface,
+        timeout,
+        ttl,
+        ident,
+        seq_cnt,
+        payload,
+        iputils_payload,
+        verify_payload,
+        nd_warmup,
+        connect,
+        max_iterations,
+        payload_size,
+        dont_fragment,
+        hop_by_hop_options,
+        destination_options,
+        verify_checksum,
+        rtt_resolution,
+        ref cancel,
+        ref accept,
+        route_check,
+        allow_self,
+        send_wallclock,
+        ..
+    } = *ping;
+
+    let has_ipv6_options = hop_by_hop_options.is_some() || destination_options.is_some();
+    if has_ipv6_options && (socket_type != Type::RAW || addr.is_ipv4()) {
+        return Err(Error::InvalidProtocol);
+    }
+
+    if cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+        return Err(Error::Cancelled);
+    }
+
+    if !allow_self && is_local(addr) {
+        return Err(Error::LocalTarget { addr });
+    }
+
     let time_start = SystemTime::now();
 
     let timeout = match timeout {
@@ -32,32 +384,47 @@ fn ping_with_socktype(
     };
 
     let dest = SocketAddr::new(addr, 0);
-    let mut buffer = [0; ECHO_REQUEST_BUFFER_SIZE];
 
-    let default_payload: &Token = &random();
+    let payload_size = payload_size.unwrap_or(TOKEN_SIZE);
+    let default_payload = if iputils_payload {
+        iputils_payload_bytes(payload_size)
+    } else if let Some(token) = &ping.token {
+        token.clone()
+    } else {
+        let mut default_payload = vec![0; payload_size];
+        rand::thread_rng().fill_bytes(&mut default_payload);
+        default_payload
+    };
+
+    let seq_cnt = seq_cnt.unwrap_or(1);
+    // keeps the allocated ident reserved until we are done waiting for the reply
+    let in_flight = match ident {
+        // a pair that is already in flight elsewhere is still sent as asked
+        Some(ident) => InFlight::reserve(ident, seq_cnt),
+        None => Some(InFlight::allocate(seq_cnt).ok_or(Error::InternalError)?),
+    };
 
     let request = EchoRequest {
-        ident: ident.unwrap_or(random()),
-        seq_cnt: seq_cnt.unwrap_or(1),
-        payload: payload.unwrap_or(default_payload),
+        ident: match (ident, &in_flight) {
+            (Some(ident), _) => ident,
+            (None, Some(in_flight)) => in_flight.ident,
+            (None, None) => return Err(Error::InternalError),
+        },
+        seq_cnt,
+        payload: match payload {
+            Some(payload) if !iputils_payload => payload,
+            _ => &default_payload,
+        },
     };
 
-    let mut socket = if dest.is_ipv4() {
-        if request.encode::<IcmpV4>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError);
-        }
-        Socket::new(Domain::IPV4, socket_type, Some(Protocol::ICMPV4))?
+    let mut buffer = vec![0; ICMP_HEADER_SIZE + request.payload.len()];
+    let encoded = if dest.is_ipv4() {
+        request.encode::<IcmpV4>(&mut buffer[..])
     } else {
-        if request.encode::<IcmpV6>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError);
-        }
-        Socket::new(Domain::IPV6, socket_type, Some(Protocol::ICMPV6))?
+        request.encode::<IcmpV6>(&mut buffer[..])
     };
-
-    socket.bind_device(iface)?;
-
-    if let Some(bind) = bind {
-        socket.bind(&SockAddr::from(SocketAddr::new(bind, 0)))?;
+    if encoded.is_err() {
+        return Err(Error::InternalError);
     }
 
     if dest.is_ipv4() {
@@ -68,35 +435,173 @@ fn ping_with_socktype(
 
     socket.set_write_timeout(Some(timeout))?;
 
-    socket.send_to(&buffer, &dest.into())?;
+    // set on every exchange since a reused socket keeps it from the last one
+    set_dont_fragment(socket, dest.is_ipv4(), dont_fragment)?;
+
+    if let (Some(warmup), IpAddr::V6(v6)) = (nd_warmup, addr) {
+        // fe80::/10
+        if v6.segments()[0] & 0xffc0 == 0xfe80 {
+            trigger_neighbor_discovery(&dest, iface, warmup)?;
+        }
+    }
+
+    let connect = connect && socket_type == Type::DGRAM;
+    if connect {
+        socket.connect(&dest.into())?;
+    }
+
+    // the source address when sending, to compare against while waiting
+    let route = match route_check {
+        Some(interval) if ping.bind.is_none() && !interval.is_zero() => {
+            route_source(ping).map(|source| (interval, source))
+        }
+        _ => None,
+    };
+
+    let send_wallclock = if send_wallclock {
+        Some(SystemTime::now())
+    } else {
+        None
+    };
+    let send_time = Instant::now();
+    let setup_time = setup_start.map(|setup_start| send_time.duration_since(setup_start));
+    if has_ipv6_options {
+        send_with_ipv6_options(
+            socket,
+            &buffer,
+            &dest,
+            hop_by_hop_options,
+            destination_options,
+        )?;
+    } else if connect {
+        socket.send(&buffer)?;
+    } else {
+        socket.send_to(&buffer, &dest.into())?;
+    }
+    ping.emit(EventKind::Sent);
+
+    // on Linux the kernel replaces the ident of DGRAM echo requests with the local port
+    let ident = if socket_type == Type::DGRAM && cfg!(target_os = "linux") {
+        match socket.local_addr()?.as_socket() {
+            Some(local) => local.port(),
+            None => return Err(Error::InternalError),
+        }
+    } else {
+        request.ident
+    };
+
+    let strategy = MatchStrategy {
+        // iputils payloads are always verified
+        verify_payload: verify_payload || iputils_payload,
+        verify_checksum,
+    };
 
     // loop until either an echo with correct ident was received or timeout is over
     let mut time_elapsed = Duration::from_secs(0);
+    let mut iterations = 0;
+    // room for the reply, its IP header and ICMP errors embedding our request
+    let mut recv_buffer = vec![0; buffer.len().max(1024) + 1024];
+    let mut last_route_check = send_time;
     loop {
-        socket.set_read_timeout(Some(timeout - time_elapsed))?;
-
-        let mut buffer: [u8; 2048] = [0; 2048];
-        socket.read(&mut buffer)?;
+        if max_iterations.is_some_and(|max_iterations| iterations >= max_iterations) {
+            return Err(Error::TooManyForeignPackets);
+        }
 
-        let reply = if dest.is_ipv4() {
-            let ipv4_packet = match IpV4Packet::decode(&buffer) {
-                Ok(packet) => packet,
-                Err(_) => return Err(Error::DecodeV4Error),
-            };
-            match EchoReply::decode::<IcmpV4>(ipv4_packet.data) {
-                Ok(reply) => reply,
-                Err(_) => continue,
+        if let Some((interval, before)) = route {
+            if last_route_check.elapsed() >= interval {
+                last_route_check = Instant::now();
+                let after = route_source(ping);
+                if after != Some(before) {
+                    return Err(Error::RouteChanged { before, after });
+                }
             }
+        }
+
+        let remaining = timeout - time_elapsed;
+        let mut wait = if cancel.is_some() {
+            // wake up regularly to check for cancellation
+            remaining.min(cancel::POLL_INTERVAL)
         } else {
-            match EchoReply::decode::<IcmpV6>(&buffer) {
-                Ok(reply) => reply,
-                Err(_) => continue,
+            remaining
+        };
+        if let Some((interval, _)) = route {
+            wait = wait.min(interval.saturating_sub(last_route_check.elapsed()));
+        }
+        // a read timeout of zero would block without any time limit
+        let wait = wait.max(MIN_WAIT);
+
+        let (size, sender) = match wait_and_recv(socket, &mut recv_buffer, wait) {
+            Ok(received) => received,
+            Err(error) if error.is_timeout() => {
+                if cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                    return Err(Error::Cancelled);
+                }
+                time_elapsed = match SystemTime::now().duration_since(time_start) {
+                    Ok(time_elapsed) => time_elapsed,
+                    Err(_) => return Err(Error::InternalError),
+                };
+                if time_elapsed >= timeout {
+                    return Err(error);
+                }
+                continue;
             }
+            Err(error) => return Err(error),
         };
+        iterations += 1;
+        let buffer = &recv_buffer[..size];
 
-        if reply.ident == request.ident {
-            // received correct ident
-            return Ok(());
+        let request = RequestMeta {
+            is_ipv4: dest.is_ipv4(),
+            socket_type,
+            ident,
+            seq_cnt: request.seq_cnt,
+            payload: request.payload,
+        };
+        let decision = decide_match(&request, buffer, sender, &strategy);
+
+        if let Some(verbose) = verbose.as_deref_mut() {
+            if let Ok((data, _)) = icmp_data(socket_type, dest.is_ipv4(), buffer) {
+                if let Some(event) =
+                    IcmpEvent::decode(dest.is_ipv4(), data, sender, send_time, ident)
+                {
+                    verbose.events.push(event);
+                }
+            }
+        }
+
+        match decision {
+            MatchDecision::Matched {
+                ttl,
+                dont_fragment,
+                checksum_valid,
+            } => {
+                let received_at = Instant::now();
+                let reply = PingReply {
+                    ident,
+                    seq_cnt: request.seq_cnt,
+                    rtt: round_rtt(measured_rtt(send_time, received_at), rtt_resolution),
+                    ttl,
+                    dont_fragment,
+                    checksum_valid,
+                    send_wallclock,
+                    received_at,
+                    setup_time,
+                };
+                let peer = SocketAddr::new(sender, 0);
+                if accept
+                    .as_ref()
+                    .is_none_or(|accept| (accept.0)(&reply, peer))
+                {
+                    return Ok(reply);
+                }
+            }
+            MatchDecision::IcmpError(error) => match verbose.as_deref_mut() {
+                // keep waiting, a reply may still follow
+                Some(verbose) if verbose.keep_waiting => verbose.last_error = Some(error),
+                _ => return Err(error),
+            },
+            MatchDecision::DecodeError => return Err(Error::DecodeV4Error),
+            MatchDecision::Duplicate | MatchDecision::Foreign => {}
         }
 
         // if ident is not correct check if timeout is over
@@ -111,6 +616,316 @@ fn ping_with_socktype(
     }
 }
 
+/// Whether `addr` belongs to the local host: a loopback address, or one a socket
+/// can be bound to, which the kernel only allows for addresses of local
+/// interfaces.
+fn is_local(addr: IpAddr) -> bool {
+    if addr.is_loopback() {
+        return true;
+    }
+    let domain = if addr.is_ipv4() {
+        Domain::IPV4
+    } else {
+        Domain::IPV6
+    };
+    match Socket::new(domain, Type::DGRAM, Some(Protocol::UDP)) {
+        Ok(socket) => socket
+            .bind(&SockAddr::from(SocketAddr::new(addr, 0)))
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Returns the source address the kernel picks for packets to the target of
+/// `ping`, from the local address of a UDP socket connected to it, or `None` if
+/// there is no route.
+fn route_source(ping: &Ping) -> Option<IpAddr> {
+    let domain = if ping.addr.is_ipv4() {
+        Domain::IPV4
+    } else {
+        Domain::IPV6
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP)).ok()?;
+    let device = ping.vrf.map(str::as_bytes).or(ping.iface);
+    socket.bind_device(device).ok()?;
+    // connecting a UDP socket only looks up the route, nothing is sent
+    let discard = SocketAddr::new(ping.addr, 9);
+    socket.connect(&discard.into()).ok()?;
+    Some(socket.local_addr().ok()?.as_socket()?.ip())
+}
+
+/// Returns the time from `send_time` to `received_at`. A reply that arrived faster than the clock
+/// advances would measure zero, which is reported as the clock resolution instead.
+fn measured_rtt(send_time: Instant, received_at: Instant) -> Duration {
+    let rtt = received_at.duration_since(send_time);
+    if rtt.is_zero() {
+        clock_resolution()
+    } else {
+        rtt
+    }
+}
+
+/// Rounds `rtt` to the nearest multiple of `resolution`, if any, but not down to
+/// zero, which is reported as `resolution` instead.
+fn round_rtt(rtt: Duration, resolution: Option<Duration>) -> Duration {
+    use std::convert::TryFrom;
+
+    let resolution = match resolution {
+        Some(resolution) if !resolution.is_zero() => resolution.as_nanos(),
+        _ => return rtt,
+    };
+    let rounded = ((rtt.as_nanos() + resolution / 2) / resolution * resolution).max(resolution);
+    Duration::from_nanos(u64::try_from(rounded).unwrap_or(u64::MAX))
+}
+
+/// Makes the kernel resolve the link layer address of `dest` by sending it a UDP
+/// datagram to the discard port from a socket bound to the same interface, then
+/// waits for `warmup`. The neighbor cache is not inspected, the wait is all there is.
+fn trigger_neighbor_discovery(
+    dest: &SocketAddr,
+    iface: Option<&[u8]>,
+    warmup: Duration,
+) -> Result<(), Error> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind_device(iface)?;
+    let discard = SocketAddr::new(dest.ip(), 9);
+    // a failed send still leaves the neighbor entry being resolved
+    let _ = socket.send_to(&[], &discard.into());
+    std::thread::sleep(warmup);
+    Ok(())
+}
+
+/// Waits up to `wait` for a packet and receives it like [`recv_from`], failing
+/// with a timeout error if none arrives.
+#[cfg(not(all(feature = "poll", unix)))]
+fn wait_and_recv(
+    socket: &Socket,
+    buffer: &mut [u8],
+    wait: Duration,
+) -> Result<(usize, IpAddr), Error> {
+    socket.set_read_timeout(Some(wait))?;
+    recv_from(socket, buffer, 0)
+}
+
+/// Waits up to `wait` for a packet with `poll(2)` instead of a socket timeout,
+/// which wakes up more precisely under load, then receives it without blocking.
+/// A wakeup without a packet is reported as a timeout, like an expired wait.
+#[cfg(all(feature = "poll", unix))]
+fn wait_and_recv(
+    socket: &Socket,
+    buffer: &mut [u8],
+    wait: Duration,
+) -> Result<(usize, IpAddr), Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut poll_fd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // round up so a short wait does not turn into a busy loop
+    let wait_ms = wait
+        .as_nanos()
+        .div_ceil(1_000_000)
+        .min(libc::c_int::MAX as u128);
+    // SAFETY: poll_fd is a single valid pollfd for the duration of the call
+    let ready = unsafe { libc::poll(&mut poll_fd, 1, wait_ms as libc::c_int) };
+    if ready == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    if ready == 0 {
+        let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout occured");
+        return Err(Error::IoError { error });
+    }
+    recv_from(socket, buffer, libc::MSG_DONTWAIT)
+}
+
+/// Receives a packet into `buffer` and returns its size and sender.
+fn recv_from(
+    socket: &Socket,
+    buffer: &mut [u8],
+    flags: std::os::raw::c_int,
+) -> Result<(usize, IpAddr), Error> {
+    // SAFETY: the buffer is initialized and recv_from only writes initialized bytes
+    let uninit = unsafe { &mut *(buffer as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    let (size, sender) = socket.recv_from_with_flags(uninit, flags)?;
+    match sender.as_socket() {
+        Some(sender) => Ok((size, sender.ip())),
+        None => Err(Error::InternalError),
+    }
+}
+
+/// Returns the ICMP message of a packet read from an ICMP socket, along with the
+/// packet's IPv4 header when it is available.
+pub(crate) fn icmp_data(
+    socket_type: Type,
+    is_ipv4: bool,
+    buffer: &[u8],
+) -> Result<(&[u8], Option<IpV4Packet<'_>>), Error> {
+    // Linux strips the IPv4 header from packets received on DGRAM sockets
+    if !is_ipv4 || (socket_type == Type::DGRAM && cfg!(target_os = "linux")) {
+        return Ok((buffer, None));
+    }
+    match IpV4Packet::decode(buffer) {
+        Ok(packet) => Ok((packet.data, Some(packet))),
+        Err(_) => Err(Error::DecodeV4Error),
+    }
+}
+
+/// Builds a payload laid out like the one sent by iputils `ping`: a `struct timeval`
+/// with the send time followed by the bytes `0x10, 0x11, ...`, each equal to its
+/// offset in the payload.
+fn iputils_payload_bytes(size: usize) -> Vec<u8> {
+    let mut payload: Vec<u8> = (0..size).map(|i| i as u8).collect();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut timeval = [0; 16];
+    timeval[..8].copy_from_slice(&(now.as_secs() as i64).to_ne_bytes());
+    timeval[8..].copy_from_slice(&i64::from(now.subsec_micros()).to_ne_bytes());
+    let len = timeval.len().min(size);
+    payload[..len].copy_from_slice(&timeval[..len]);
+    payload
+}
+
+/// Sets the don't fragment bit on outgoing packets and disables fragmentation, or
+/// goes back to the kernel default of fragmenting as needed.
+#[cfg(target_os = "linux")]
+fn set_dont_fragment(socket: &Socket, is_ipv4: bool, dont_fragment: bool) -> Result<(), Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let (level, name, on, off) = if is_ipv4 {
+        (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_DO,
+            libc::IP_PMTUDISC_WANT,
+        )
+    } else {
+        (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_DO,
+            libc::IPV6_PMTUDISC_WANT,
+        )
+    };
+    let value = if dont_fragment { on } else { off };
+    // SAFETY: the option value is a c_int that outlives the call
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_dont_fragment(_socket: &Socket, _is_ipv4: bool, dont_fragment: bool) -> Result<(), Error> {
+    if !dont_fragment {
+        return Ok(());
+    }
+    let error = std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "don't fragment is only supported on Linux",
+    );
+    Err(Error::IoError { error })
+}
+
+/// Sends `buffer` to `dest` with the given IPv6 extension headers attached as
+/// ancillary data.
+#[cfg(target_os = "linux")]
+fn send_with_ipv6_options(
+    socket: &Socket,
+    buffer: &[u8],
+    dest: &SocketAddr,
+    hop_by_hop_options: Option<&[u8]>,
+    destination_options: Option<&[u8]>,
+) -> Result<usize, Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let options: Vec<(libc::c_int, &[u8])> = vec![
+        (libc::IPV6_HOPOPTS, hop_by_hop_options),
+        (libc::IPV6_DSTOPTS, destination_options),
+    ]
+    .into_iter()
+    .filter_map(|(kind, options)| Some((kind, options?)))
+    .collect();
+
+    // SAFETY: CMSG_SPACE only computes a size
+    let control_len: usize = options
+        .iter()
+        .map(|(_, data)| unsafe { libc::CMSG_SPACE(data.len() as u32) } as usize)
+        .sum();
+    // u64 keeps the control buffer aligned for cmsghdr
+    let mut control = vec![0u64; control_len.div_ceil(8)];
+    let dest = SockAddr::from(*dest);
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_ptr() as *mut libc::c_void,
+        iov_len: buffer.len(),
+    };
+
+    // SAFETY: all pointers in the msghdr point to buffers that outlive the call,
+    // and the control buffer was sized with CMSG_SPACE for every header written
+    let sent = unsafe {
+        let mut message: libc::msghdr = std::mem::zeroed();
+        message.msg_name = dest.as_ptr() as *mut libc::c_void;
+        message.msg_namelen = dest.len();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        message.msg_controllen = control_len as _;
+
+        let mut header = libc::CMSG_FIRSTHDR(&message);
+        for (kind, data) in &options {
+            if header.is_null() {
+                return Err(Error::InternalError);
+            }
+            (*header).cmsg_level = libc::IPPROTO_IPV6;
+            (*header).cmsg_type = *kind;
+            (*header).cmsg_len = libc::CMSG_LEN(data.len() as u32) as _;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), libc::CMSG_DATA(header), data.len());
+            header = libc::CMSG_NXTHDR(&message, header);
+        }
+
+        libc::sendmsg(socket.as_raw_fd(), &message, 0)
+    };
+    if sent == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(sent as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_with_ipv6_options(
+    _socket: &Socket,
+    _buffer: &[u8],
+    _dest: &SocketAddr,
+    _hop_by_hop_options: Option<&[u8]>,
+    _destination_options: Option<&[u8]>,
+) -> Result<usize, Error> {
+    let error = std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "IPv6 extension headers are only supported on Linux",
+    );
+    Err(Error::IoError { error })
+}
+
+pub(crate) fn original_ident(is_ipv4: bool, original: &[u8]) -> Option<u16> {
+    if is_ipv4 {
+        let packet = IpV4Packet::decode(original).ok()?;
+        echo_request_ident::<IcmpV4>(packet.data)
+    } else {
+        echo_request_ident::<IcmpV6>(original.get(IPV6_HEADER_SIZE..)?)
+    }
+}
+
 pub mod rawsock {
     use super::*;
 
@@ -124,7 +939,7 @@ pub mod rawsock {
         ident: Option<u16>,
         seq_cnt: Option<u16>,
         payload: Option<&Token>,
-    ) -> Result<(), Error> {
+    ) -> Result<PingReply, Error> {
         ping_with_socktype(
             Type::RAW,
             addr,
@@ -152,7 +967,7 @@ pub mod dgramsock {
         ident: Option<u16>,
         seq_cnt: Option<u16>,
         payload: Option<&Token>,
-    ) -> Result<(), Error> {
+    ) -> Result<PingReply, Error> {
         ping_with_socktype(
             Type::DGRAM,
             addr,
@@ -177,21 +992,60 @@ pub fn ping(
     ident: Option<u16>,
     seq_cnt: Option<u16>,
     payload: Option<&Token>,
-) -> Result<(), Error> {
+) -> Result<PingReply, Error> {
     rawsock::ping(addr, iface, bind, timeout, ttl, ident, seq_cnt, payload)
 }
 
-#[derive(Debug)]
+type Predicate = dyn Fn(&PingReply, SocketAddr) -> bool + Send + Sync;
+
+/// A predicate set with [`Ping::accept`].
+#[derive(Clone)]
+struct AcceptFn(Arc<Predicate>);
+
+impl fmt::Debug for AcceptFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AcceptFn")
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Ping<'a> {
-    socket_type: Type,
-    addr: IpAddr,
+    pub(crate) socket_type: Type,
+    pub(crate) addr: IpAddr,
     iface: Option<&'a [u8]>,
     bind: Option<IpAddr>,
     timeout: Option<Duration>,
     ttl: Option<u32>,
-    ident: Option<u16>,
-    seq_cnt: Option<u16>,
-    payload: Option<&'a Token>,
+    pub(crate) ident: Option<u16>,
+    pub(crate) seq_cnt: Option<u16>,
+    pub(crate) payload: Option<&'a Token>,
+    iputils_payload: bool,
+    verify_payload: bool,
+    nd_warmup: Option<Duration>,
+    connect: bool,
+    max_iterations: Option<usize>,
+    pub(crate) payload_size: Option<usize>,
+    /// Sent instead of a random payload, e.g. the per probe token of a stream.
+    pub(crate) token: Option<Vec<u8>>,
+    dont_fragment: bool,
+    hop_by_hop_options: Option<&'a [u8]>,
+    destination_options: Option<&'a [u8]>,
+    verify_checksum: bool,
+    bind_order: BindOrder,
+    vrf: Option<&'a str>,
+    rtt_resolution: Option<Duration>,
+    pub(crate) cancel: Option<CancelToken>,
+    accept: Option<AcceptFn>,
+    events: Option<Sender<PingEvent>>,
+    route_check: Option<Duration>,
+    allow_self: bool,
+    send_wallclock: bool,
+    measure_setup: bool,
+    source_ports: Option<RangeInclusive<u16>>,
+    health_thresholds: HealthThresholds,
+    /// Sums up the packets every socket the ping is sent on dropped for a full
+    /// receive buffer while waiting, for the local drops of a burst.
+    socket_drops: Option<Arc<AtomicU64>>,
 }
 
 impl<'a> Ping<'a> {
@@ -211,9 +1065,38 @@ impl<'a> Ping<'a> {
             ident: None,
             seq_cnt: None,
             payload: None,
+            iputils_payload: false,
+            verify_payload: false,
+            nd_warmup: None,
+            connect: false,
+            max_iterations: None,
+            payload_size: None,
+            token: None,
+            dont_fragment: false,
+            hop_by_hop_options: None,
+            destination_options: None,
+            verify_checksum: false,
+            bind_order: BindOrder::DeviceFirst,
+            vrf: None,
+            rtt_resolution: None,
+            cancel: None,
+            accept: None,
+            events: None,
+            route_check: None,
+            allow_self: true,
+            send_wallclock: false,
+            measure_setup: false,
+            source_ports: None,
+            health_thresholds: HealthThresholds::default(),
+            socket_drops: None,
         }
     }
 
+    pub(crate) fn addr(mut self, addr: IpAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
     pub fn socket_type(mut self, socket_type: Type) -> Self {
         self.socket_type = socket_type;
         self
@@ -229,6 +1112,47 @@ impl<'a> Ping<'a> {
         self
     }
 
+    /// Binds DGRAM sockets to a local port from `ports`, together with the
+    /// [`bind`](Ping::bind) address if one is set. Linux uses the local port of a
+    /// DGRAM ICMP socket as the ident of its echo requests, so this keeps the ident
+    /// within `ports`, e.g. for firewalls that filter ICMP by ident. The first port
+    /// is picked at random, and while ports are taken by other ICMP sockets the
+    /// next ones are tried, wrapping around at the end of the range; if all are
+    /// taken, pinging fails with `AddrInUse`. The socket is bound without address
+    /// reuse, which Linux otherwise enables on ICMP sockets, so no other socket
+    /// shares its port and thereby its ident. Has no effect on raw sockets, and on other
+    /// platforms the port does not become the ident.
+    pub fn source_ports(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.source_ports = Some(ports);
+        self
+    }
+
+    /// Sends the request through the routing table of the VRF device `vrf`, by
+    /// binding the socket to the VRF master device the way `ip vrf exec` does.
+    /// Unlike [`iface`](Ping::iface) with a regular interface this does not pin the
+    /// outgoing interface, the VRF's routes pick it.
+    ///
+    /// Only supported on Linux. Pinging fails if `vrf` is not a VRF device or if an
+    /// interface is set as well; to use an interface enslaved to a VRF, set only the
+    /// interface.
+    pub fn vrf(mut self, vrf: &'a str) -> Self {
+        self.vrf = Some(vrf);
+        self
+    }
+
+    /// Sets whether the socket is bound to the interface or to the bind address
+    /// first. On multi-homed hosts some platforms pick a different source address
+    /// or route depending on this order, so pinning it makes source selection
+    /// deterministic. Linux applies both restrictions when sending either way,
+    /// but checks the bind address against an interface that is already set, so
+    /// e.g. binding an IPv6 socket restricted to `eth0` to `::1` fails with
+    /// `AddrNotAvailable` unless the address is bound first. Only matters when both
+    /// [`iface`](Ping::iface) and [`bind`](Ping::bind) are set.
+    pub fn bind_order(mut self, bind_order: BindOrder) -> Self {
+        self.bind_order = bind_order;
+        self
+    }
+
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.timeout = Some(timeout);
         self
@@ -254,17 +1178,375 @@ impl<'a> Ping<'a> {
         self
     }
 
-    pub fn ping(self) -> Result<(), Error> {
-        ping_with_socktype(
-            self.socket_type,
-            self.addr,
-            self.iface,
-            self.bind,
-            self.timeout,
-            self.ttl,
-            self.ident,
-            self.seq_cnt,
-            self.payload,
-        )
+    /// Sends generated payloads of `payload_size` bytes instead of 24. Has no
+    /// effect when a payload is given.
+    pub fn payload_size(mut self, payload_size: usize) -> Self {
+        self.payload_size = Some(payload_size);
+        self
+    }
+
+    /// Sets the don't fragment bit so routers drop the request instead of
+    /// fragmenting it when it exceeds a link's MTU, and report that with
+    /// [`Error::PacketTooBig`]. Only supported on Linux.
+    pub fn dont_fragment(mut self, dont_fragment: bool) -> Self {
+        self.dont_fragment = dont_fragment;
+        self
+    }
+
+    /// Attaches a hop-by-hop options extension header to the echo request, e.g. to
+    /// test how routers on the path handle it. `options` is the encoded header as
+    /// described in RFC 8200, including its next header and length bytes, and must
+    /// be a multiple of 8 bytes long; the kernel fills in the next header.
+    ///
+    /// Only raw sockets and IPv6 targets support extension headers, pinging fails
+    /// with [`Error::InvalidProtocol`] otherwise. They are passed as ancillary
+    /// data, which is only implemented on Linux.
+    pub fn ipv6_hop_by_hop_options(mut self, options: &'a [u8]) -> Self {
+        self.hop_by_hop_options = Some(options);
+        self
+    }
+
+    /// Attaches a destination options extension header to the echo request, with
+    /// the same encoding and restrictions as
+    /// [`ipv6_hop_by_hop_options`](Ping::ipv6_hop_by_hop_options).
+    pub fn ipv6_destination_options(mut self, options: &'a [u8]) -> Self {
+        self.destination_options = Some(options);
+        self
+    }
+
+    /// Sends a [`PingEvent`] to `events` when the request was sent and when it got
+    /// a reply, timed out or failed, as it happens. The pings of a
+    /// [`stream`](Ping::stream) or [`Batch`](crate::Batch) configured with this
+    /// option all report to the same channel. The channel is unbounded, so sending
+    /// never blocks the ping and events pile up if the receiver falls behind; once
+    /// the receiver is dropped the events are discarded.
+    pub fn events(mut self, events: Sender<PingEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn emit(&self, kind: EventKind) {
+        if let Some(events) = &self.events {
+            let event = PingEvent {
+                addr: self.addr,
+                seq_cnt: self.seq_cnt.unwrap_or(1),
+                kind,
+            };
+            // a dropped receiver is not our concern
+            let _ = events.send(event);
+        }
+    }
+
+    /// Only accepts replies for which `accept` returns `true`, e.g. to require a
+    /// sender from a certain subnet. The predicate gets the reply and its sender,
+    /// with port 0, and only runs for replies that passed the built-in checks of
+    /// ident, sequence number, and payload and checksum if verified. Rejected
+    /// replies are ignored like replies to other requests. It runs in the receive
+    /// loop, so it has to be fast.
+    pub fn accept(
+        mut self,
+        accept: impl Fn(&PingReply, SocketAddr) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.accept = Some(AcceptFn(Arc::new(accept)));
+        self
+    }
+
+    /// Whether the target may be the local host, i.e. a loopback address or an
+    /// address assigned to a local interface. Defaults to `true`; set it to `false`
+    /// to fail with [`Error::LocalTarget`] before sending, e.g. to catch a
+    /// misconfigured target list that would make a monitor report the host itself
+    /// as reachable.
+    ///
+    /// The local host answers over the loopback path, so self pings succeed with
+    /// an RTT near zero even without a working network. On Linux, DGRAM sockets
+    /// still get the ident replaced by the local port and match the reply by it,
+    /// and raw sockets see their own echo request as well as the reply, which is
+    /// ignored since it is not an echo reply.
+    pub fn allow_self(mut self, allow_self: bool) -> Self {
+        self.allow_self = allow_self;
+        self
+    }
+
+    /// Reports the wall clock time the request was sent at in
+    /// [`PingReply::send_wallclock`]. Off by default to save reading the clock.
+    pub fn send_wallclock(mut self, send_wallclock: bool) -> Self {
+        self.send_wallclock = send_wallclock;
+        self
+    }
+
+    /// Reports the time spent setting up the socket before sending in
+    /// [`PingReply::setup_time`], e.g. to see what reusing a
+    /// [`PingSocket`](crate::PingSocket) would save. Off by default to save reading
+    /// the clock.
+    pub fn measure_setup(mut self, measure_setup: bool) -> Self {
+        self.measure_setup = measure_setup;
+        self
+    }
+
+    /// Checks every `interval` while waiting for the reply whether the source address
+    /// the kernel picks for the target changed since sending, and if so fails with
+    /// [`Error::RouteChanged`] instead of waiting for a reply that will likely never
+    /// match, e.g. on a roaming host that switched networks.
+    ///
+    /// The source address is looked up by connecting a UDP socket, bound to the
+    /// same interface or VRF, to the target, which consults the routing table
+    /// without sending anything. Changes that keep the source address, like a new
+    /// gateway on the same interface, go unnoticed, as do changes that revert
+    /// within `interval`. Pings with a [`bind`](Ping::bind) address are not
+    /// checked, since their source address cannot change. An `interval` of zero
+    /// disables the check.
+    pub fn route_check(mut self, interval: Duration) -> Self {
+        self.route_check = Some(interval);
+        self
+    }
+
+    /// Stops the ping with [`Error::Cancelled`] once `token` is cancelled, checking
+    /// it before sending and every 50 ms while waiting for the reply. A
+    /// [`stream`](Ping::stream) of a cancelled ping ends without yielding the
+    /// cancelled probe, so its statistics only cover finished probes, while its
+    /// recorder notes the probe as cancelled. A cancelled ping releases its ident
+    /// and closes its socket, so replies arriving afterwards are not matched.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Rounds the reported [`PingReply::rtt`] to the nearest multiple of
+    /// `resolution`, e.g. to milliseconds before publishing RTTs so they do not leak
+    /// fine grained timing, or to keep a display from flickering. The RTT is still
+    /// measured precisely, only the reported value is rounded, so everything built
+    /// on replies, like [`PingStatistics`](crate::PingStatistics) of a
+    /// [`burst`](Ping::burst), sees the rounded values.
+    pub fn rtt_resolution(mut self, resolution: Duration) -> Self {
+        self.rtt_resolution = Some(resolution);
+        self
+    }
+
+    /// Ignores replies with a bad ICMP checksum instead of accepting them and
+    /// reporting the bad checksum in [`PingReply::checksum_valid`].
+    pub fn verify_checksum(mut self, verify_checksum: bool) -> Self {
+        self.verify_checksum = verify_checksum;
+        self
+    }
+
+    /// Sends the payload iputils `ping` would send (send timestamp followed by an
+    /// incrementing byte pattern) instead of a random or user supplied one, so
+    /// packet captures are decoded like regular `ping` traffic. Replies must echo
+    /// the payload unchanged to be accepted.
+    pub fn iputils_payload(mut self, iputils_payload: bool) -> Self {
+        self.iputils_payload = iputils_payload;
+        self
+    }
+
+    /// Only accepts replies that echo the payload unchanged.
+    pub fn verify_payload(mut self, verify_payload: bool) -> Self {
+        self.verify_payload = verify_payload;
+        self
+    }
+
+    /// Before pinging a link-local IPv6 address, triggers neighbor discovery for it
+    /// and waits `warmup` so the echo request is not lost while the kernel resolves
+    /// the neighbor. Link-local targets need `iface` to select the link.
+    ///
+    /// Neighbor discovery is triggered by sending an empty UDP datagram to the
+    /// target's discard port, which makes the kernel send a neighbor solicitation
+    /// like for any other packet. Whether resolution finished in time is not
+    /// checked.
+    pub fn nd_warmup(mut self, warmup: Duration) -> Self {
+        self.nd_warmup = Some(warmup);
+        self
+    }
+
+    /// Connects DGRAM sockets to the target before sending, instead of passing the
+    /// target to every send. The kernel then picks the source address once, when
+    /// connecting, and only delivers packets from the target to the socket, so
+    /// replies from other hosts never reach the receive loop. Has no effect on raw
+    /// sockets.
+    pub fn connect(mut self, connect: bool) -> Self {
+        self.connect = connect;
+        self
+    }
+
+    /// Gives up with [`Error::TooManyForeignPackets`] after reading
+    /// `max_iterations` packets that are not our reply, independent of the
+    /// timeout. This bounds the work done when the socket is flooded with other
+    /// ICMP traffic.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn ping(self) -> Result<PingReply, Error> {
+        crate::otel::in_span(self.addr, || send_echo_request(&self))
+    }
+
+    /// Pings the target and, if it answers, checks its forward confirmed reverse
+    /// DNS: the PTR name of the address is looked up, then the addresses of that
+    /// name, and the check passes if they include the target again. A mismatch
+    /// often means stale or spoofed reverse DNS.
+    ///
+    /// Both lookups use the system resolver and together are bounded by
+    /// `dns_timeout`; a lookup that takes longer keeps running on a background
+    /// thread, but its result is ignored. Reverse lookups are only supported on
+    /// Unix and fail elsewhere.
+    pub fn ping_fcrdns(self, dns_timeout: Duration) -> Result<FcrdnsReply, Error> {
+        let addr = self.addr;
+        let reply = self.ping()?;
+        Ok(FcrdnsReply {
+            reply,
+            fcrdns: dns::fcrdns(addr, dns_timeout),
+        })
+    }
+
+    /// Pings like [`ping`](Ping::ping), but collects every ICMP error message about
+    /// the request that arrives before the reply or the timeout, e.g. a redirect
+    /// followed by a time exceeded message, instead of returning on the first one.
+    ///
+    /// The outcome is the reply if one arrives. Otherwise it is the error the last
+    /// of the collected messages maps to, or the timeout if none maps to an error,
+    /// so waiting takes the full timeout unless a reply arrives. Only raw sockets
+    /// receive ICMP error messages on Linux.
+    pub fn ping_verbose(self) -> (Result<PingReply, Error>, Vec<IcmpEvent>) {
+        self.ping_collecting(true)
+    }
+
+    /// Pings while collecting the ICMP error messages about the request, returning
+    /// on the first one that maps to an error unless `keep_waiting` is set.
+    pub(crate) fn ping_collecting(
+        self,
+        keep_waiting: bool,
+    ) -> (Result<PingReply, Error>, Vec<IcmpEvent>) {
+        let mut verbose = Verbose {
+            keep_waiting,
+            ..Verbose::default()
+        };
+        let result = crate::otel::in_span(self.addr, || {
+            let setup_start = setup_start(&self);
+            let socket = match open_socket(&self) {
+                Ok(socket) => socket,
+                Err(error) => {
+                    let result = Err(error);
+                    self.emit(EventKind::from_result(&result));
+                    return result;
+                }
+            };
+            exchange_with(&self, &socket, setup_start, Some(&mut verbose))
+        });
+        (result, verbose.events)
+    }
+
+    /// Pings the target once every `interval`, starting immediately, with an
+    /// increasing sequence number.
+    pub fn stream(self, interval: Duration) -> PingStream<'a> {
+        PingStream::new(self, interval)
+    }
+
+    /// Sets the thresholds [`burst`](Ping::burst) classifies the target's
+    /// [`Burst::health`] with. Defaults to [`HealthThresholds::default`].
+    pub fn health_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.health_thresholds = thresholds;
+        self
+    }
+
+    /// Sends `count` pings, one every `interval`, and summarizes them. Stops early
+    /// on errors that are not transient.
+    pub fn burst(mut self, count: usize, interval: Duration) -> Burst {
+        let counters = DropCounters::read();
+        let socket_drops = Arc::new(AtomicU64::new(0));
+        self.socket_drops = Some(Arc::clone(&socket_drops));
+        let thresholds = self.health_thresholds;
+        let results: Vec<_> = self.stream(interval).take(count).collect();
+        summarize(results, counters, &socket_drops, &thresholds)
+    }
+
+    /// Sends a ping every `interval` until the confidence interval of the mean RTT
+    /// is as narrow as `target` asks, or `target.max_probes` were sent, and
+    /// summarizes them like [`burst`](Ping::burst). Stops early on errors that are
+    /// not transient.
+    pub fn burst_until_confident(mut self, interval: Duration, target: &ConfidenceTarget) -> Burst {
+        let counters = DropCounters::read();
+        let socket_drops = Arc::new(AtomicU64::new(0));
+        self.socket_drops = Some(Arc::clone(&socket_drops));
+        let thresholds = self.health_thresholds;
+        let mut accumulator = RttAccumulator::default();
+        let mut results = Vec::new();
+        for result in self.stream(interval).take(target.max_probes) {
+            if let Ok(reply) = &result {
+                accumulator.push(reply.rtt);
+            }
+            results.push(result);
+            let narrow = accumulator
+                .half_width(target.z)
+                .is_some_and(|half_width| half_width <= target.half_width);
+            if accumulator.count() >= target.min_replies.max(2) && narrow {
+                break;
+            }
+        }
+        summarize(results, counters, &socket_drops, &thresholds)
+    }
+}
+
+/// Computes the statistics of a burst, with the local drops since `counters` and
+/// the drops of its sockets.
+fn summarize(
+    results: Vec<Result<PingReply, Error>>,
+    counters: Option<DropCounters>,
+    socket_drops: &AtomicU64,
+    thresholds: &HealthThresholds,
+) -> Burst {
+    let mut statistics = PingStatistics::from_results(&results);
+    statistics.local_drops = match (counters, DropCounters::read()) {
+        (Some(before), Some(after)) => {
+            Some(after.drops_since(&before) + socket_drops.load(Ordering::Relaxed))
+        }
+        _ => None,
+    };
+    Burst {
+        health: statistics.health_with(thresholds),
+        results,
+        statistics,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    fn unix_secs() -> i64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    #[test]
+    fn iputils_payload_layout() {
+        let before = unix_secs();
+        let payload = iputils_payload_bytes(56);
+        assert_eq!(payload.len(), 56);
+
+        let secs = i64::from_ne_bytes(payload[..8].try_into().unwrap());
+        let micros = i64::from_ne_bytes(payload[8..16].try_into().unwrap());
+        assert!((before..=unix_secs()).contains(&secs));
+        assert!((0..1_000_000).contains(&micros));
+        // the pattern after the timeval starts at 0x10, its own offset
+        for (offset, byte) in payload.iter().enumerate().skip(16) {
+            assert_eq!(*byte, offset as u8);
+        }
+    }
+
+    #[test]
+    fn iputils_payload_shorter_than_timeval() {
+        assert!(iputils_payload_bytes(0).is_empty());
+        // the timeval is cut off and no pattern follows
+        let before = unix_secs();
+        let payload = iputils_payload_bytes(8);
+        assert_eq!(payload.len(), 8);
+        let secs = i64::from_ne_bytes(payload[..].try_into().unwrap());
+        assert!((before..=unix_secs()).contains(&secs));
+        assert_eq!(iputils_payload_bytes(15).len(), 15);
+        assert_eq!(iputils_payload_bytes(17)[16], 0x10);
     }
 }
@@ -0,0 +1,78 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::errors::Error;
+use crate::otel::Parent;
+use crate::ping::{Ping, PingReply};
+
+type Job = (Ping<'static>, Parent, Sender<Result<PingReply, Error>>);
+
+/// A fixed set of worker threads that execute submitted pings.
+///
+/// Dropping the pool stops accepting new pings, lets the workers finish every
+/// ping that was already submitted, and then joins the worker threads. Results
+/// of pings that were queued before the drop are still delivered to their
+/// receivers.
+#[derive(Debug)]
+pub struct PingPool {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl PingPool {
+    /// Spawns a pool with `workers` threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "ping pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..workers)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok((ping, parent, result)) => {
+                            // the caller may have dropped its receiver already
+                            let _ = result.send(parent.in_scope(|| ping.ping()));
+                        }
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+
+        PingPool {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queues `ping` for execution and returns a receiver for its result.
+    pub fn submit(&self, ping: Ping<'static>) -> Receiver<Result<PingReply, Error>> {
+        let (result, receiver) = mpsc::channel();
+        if let Some(sender) = &self.sender {
+            // workers only exit after the sender is dropped, so this cannot fail
+            let _ = sender.send((ping, Parent::capture(), result));
+        }
+        receiver
+    }
+}
+
+impl Drop for PingPool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::errors::Error;
+use crate::ping::PingReply;
+
+/// What happened to a probe.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum EventKind {
+    Sent,
+    Reply {
+        rtt: Duration,
+    },
+    Timeout,
+    Error {
+        message: String,
+        /// See [`Error::raw_os_error`].
+        raw_os_error: Option<i32>,
+    },
+}
+
+impl EventKind {
+    pub(crate) fn from_result(result: &Result<PingReply, Error>) -> Self {
+        match result {
+            Ok(reply) => EventKind::Reply { rtt: reply.rtt },
+            Err(error) if error.is_timeout() => EventKind::Timeout,
+            Err(error) => EventKind::Error {
+                message: error.to_string(),
+                raw_os_error: error.raw_os_error(),
+            },
+        }
+    }
+}
+
+/// An event of a ping sent to the channel given to
+/// [`Ping::events`](crate::Ping::events).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PingEvent {
+    pub addr: IpAddr,
+    pub seq_cnt: u16,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RecordedEvent {
+    pub time: SystemTime,
+    pub seq_cnt: u16,
+    pub kind: EventKind,
+}
+
+/// Keeps the most recent events of a [`PingStream`](crate::PingStream).
+///
+/// The recorder holds at most `capacity` events, two per probe, so memory stays
+/// bounded on long runs: once full, the oldest events are dropped and counted in
+/// [`dropped`](Recorder::dropped). With the `serde` feature the events can be
+/// serialized to any format, [`export_csv`](Recorder::export_csv) needs no
+/// feature.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    events: VecDeque<RecordedEvent>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl Recorder {
+    pub fn new(capacity: usize) -> Self {
+        Recorder {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, seq_cnt: u16, kind: EventKind) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(RecordedEvent {
+            time: SystemTime::now(),
+            seq_cnt,
+            kind,
+        });
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &RecordedEvent> {
+        self.events.iter()
+    }
+
+    /// The number of events dropped because the recorder was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Writes the events as CSV with the columns `time` (microseconds since the
+    /// Unix epoch), `seq`, `event`, `rtt_us`, `error` and `errno`.
+    pub fn export_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "time,seq,event,rtt_us,error,errno")?;
+        for event in &self.events {
+            let time = event
+                .time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros();
+            let (name, rtt, error, errno) = match &event.kind {
+                EventKind::Sent => ("sent", String::new(), String::new(), String::new()),
+                EventKind::Reply { rtt } => (
+                    "reply",
+                    rtt.as_micros().to_string(),
+                    String::new(),
+                    String::new(),
+                ),
+                EventKind::Timeout => ("timeout", String::new(), String::new(), String::new()),
+                EventKind::Error {
+                    message,
+                    raw_os_error,
+                } => (
+                    "error",
+                    String::new(),
+                    format!("\"{}\"", message.replace('"', "\"\"")),
+                    raw_os_error
+                        .map(|errno| errno.to_string())
+                        .unwrap_or_default(),
+                ),
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                time, event.seq_cnt, name, rtt, error, errno
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::errors::{map_would_block, Error};
+use crate::message::{IcmpMessage, IcmpReply, IcmpVersion, RawIcmp};
+use crate::packet::EchoRequest;
+use crate::ping::{exchange, icmp_data, Ping, PingReply};
+
+/// An echo message received on a [`PingSocket`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EchoPacket {
+    pub ident: u16,
+    pub seq_cnt: u16,
+    pub payload: Vec<u8>,
+}
+
+/// A pair of ICMP sockets, one per address family, that can be used for many
+/// pings.
+///
+/// Each socket is only opened when first used for an address of its family, so a
+/// `PingSocket` that is used for IPv4 and IPv6 targets holds two file
+/// descriptors. Operations on the same family are serialized.
+///
+/// [`ping`](PingSocket::ping) sends a request and waits for its reply. `send` and
+/// `recv` are a lower level building block: they transfer single echo messages
+/// and leave matching replies to requests to the caller. In non-blocking mode both
+/// return [`Error::WouldBlock`] instead of waiting.
+///
+/// On Linux the kernel replaces the ident of echo requests sent on a DGRAM socket
+/// with the socket's local port, so replies carry that ident instead.
+#[derive(Debug)]
+pub struct PingSocket {
+    socket_type: Type,
+    v4: Mutex<Option<Socket>>,
+    v6: Mutex<Option<Socket>>,
+    options: Mutex<SocketOptions>,
+    seq_cnt: AtomicU16,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SocketOptions {
+    nonblocking: bool,
+    read_timeout: Option<Duration>,
+}
+
+impl SocketOptions {
+    fn apply(&self, socket: &Socket) -> Result<(), Error> {
+        socket.set_nonblocking(self.nonblocking)?;
+        socket.set_read_timeout(self.read_timeout)?;
+        Ok(())
+    }
+}
+
+impl PingSocket {
+    /// Creates a socket of `socket_type`. Nothing is opened until the first use.
+    pub fn new(socket_type: Type) -> Self {
+        PingSocket {
+            socket_type,
+            v4: Mutex::new(None),
+            v6: Mutex::new(None),
+            options: Mutex::new(SocketOptions::default()),
+            seq_cnt: AtomicU16::new(1),
+        }
+    }
+
+    fn options(&self) -> Result<SocketOptions, Error> {
+        match self.options.lock() {
+            Ok(options) => Ok(*options),
+            Err(_) => Err(Error::InternalError),
+        }
+    }
+
+    fn update_options(&self, update: impl FnOnce(&mut SocketOptions)) -> Result<(), Error> {
+        let options = match self.options.lock() {
+            Ok(mut options) => {
+                update(&mut options);
+                *options
+            }
+            Err(_) => return Err(Error::InternalError),
+        };
+        for socket in [&self.v4, &self.v6] {
+            let socket = socket.lock().map_err(|_| Error::InternalError)?;
+            if let Some(socket) = socket.as_ref() {
+                options.apply(socket)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with the socket for the family of `addr`, opening it if needed.
+    fn with_socket<T>(
+        &self,
+        addr: IpAddr,
+        f: impl FnOnce(&Socket) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let (socket, domain, protocol) = if addr.is_ipv4() {
+            (&self.v4, Domain::IPV4, Protocol::ICMPV4)
+        } else {
+            (&self.v6, Domain::IPV6, Protocol::ICMPV6)
+        };
+        let mut socket = socket.lock().map_err(|_| Error::InternalError)?;
+        if socket.is_none() {
+            let new = Socket::new(domain, self.socket_type, Some(protocol))?;
+            self.options()?.apply(&new)?;
+            *socket = Some(new);
+        }
+        match socket.as_ref() {
+            Some(socket) => f(socket),
+            None => Err(Error::InternalError),
+        }
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), Error> {
+        self.update_options(|options| options.nonblocking = nonblocking)
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), Error> {
+        self.update_options(|options| options.read_timeout = timeout)
+    }
+
+    /// Pings the target of `ping` with its options, reusing the socket of the
+    /// target's family. The socket type, interface, VRF, bind address and connect
+    /// options of `ping` are ignored since the socket is shared, and pings without an
+    /// explicit sequence number get one from a counter of this socket so late replies
+    /// to earlier pings are not mistaken for replies to later ones.
+    ///
+    /// A socket in non-blocking mode is switched to blocking mode while the ping
+    /// waits for its reply and back afterwards.
+    pub fn ping(&self, ping: Ping) -> Result<PingReply, Error> {
+        let seq_cnt = match ping.seq_cnt {
+            Some(seq_cnt) => seq_cnt,
+            None => self.seq_cnt.fetch_add(1, Ordering::Relaxed),
+        };
+        let ping = ping
+            .socket_type(self.socket_type)
+            .seq_cnt(seq_cnt)
+            .connect(false);
+        let addr = ping.addr;
+        self.with_socket(addr, |socket| {
+            socket.set_nonblocking(false)?;
+            let result = crate::otel::in_span(addr, || exchange(&ping, socket));
+            // restores non-blocking mode and the read timeout the exchange changed
+            self.options()?.apply(socket)?;
+            result
+        })
+    }
+
+    /// Sends an echo request to `addr`.
+    pub fn send(
+        &self,
+        addr: IpAddr,
+        ident: u16,
+        seq_cnt: u16,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        let request = EchoRequest {
+            ident,
+            seq_cnt,
+            payload,
+        };
+        self.send_message(addr, &request)
+    }
+
+    /// Receives one echo reply on the socket for the family of `addr`, which may
+    /// come from any host. Other ICMP messages are reported as
+    /// [`Error::DecodeEchoReplyError`].
+    pub fn recv(&self, addr: IpAddr) -> Result<EchoPacket, Error> {
+        self.recv_message(addr)
+    }
+
+    /// Sends an arbitrary ICMP message to `addr`, see [`RawIcmp`].
+    pub fn send_raw(&self, addr: IpAddr, message: &RawIcmp) -> Result<(), Error> {
+        self.send_message(addr, message)
+    }
+
+    /// Receives the next ICMP message of any type on the socket for the family of
+    /// `addr`.
+    pub fn recv_raw(&self, addr: IpAddr) -> Result<RawIcmp, Error> {
+        self.recv_message(addr)
+    }
+
+    /// Sends `message` to `addr`.
+    pub fn send_message<M: IcmpMessage>(&self, addr: IpAddr, message: &M) -> Result<(), Error> {
+        let mut buffer = vec![0; message.encoded_len()];
+        let size = message.encode(IcmpVersion::of(addr), &mut buffer)?;
+        self.with_socket(addr, |socket| {
+            socket
+                .send_to(&buffer[..size], &SocketAddr::new(addr, 0).into())
+                .map_err(map_would_block)?;
+            Ok(())
+        })
+    }
+
+    /// Receives the next ICMP message on the socket for the family of `addr` and
+    /// decodes it as `R`.
+    pub fn recv_message<R: IcmpReply>(&self, addr: IpAddr) -> Result<R, Error> {
+        let mut buffer: [u8; 2048] = [0; 2048];
+        let size = self.with_socket(addr, |mut socket| {
+            socket.read(&mut buffer).map_err(map_would_block)
+        })?;
+        let (data, _) = icmp_data(self.socket_type, addr.is_ipv4(), &buffer[..size])?;
+        R::decode(IcmpVersion::of(addr), data)
+    }
+
+    /// Sends `message` to `addr` and returns the first received message that
+    /// decodes as its reply type. Replies are not matched to the request beyond
+    /// their type, and the read timeout bounds each wait for a message, not the
+    /// whole exchange.
+    pub fn request<M: IcmpMessage>(&self, addr: IpAddr, message: &M) -> Result<M::Reply, Error> {
+        self.send_message(addr, message)?;
+        loop {
+            match self.recv_message::<M::Reply>(addr) {
+                Ok(reply) => return Ok(reply),
+                Err(error @ Error::IoError { .. }) | Err(error @ Error::WouldBlock) => {
+                    return Err(error)
+                }
+                Err(_) => continue,
+            }
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::ping::PingReply;
+
+/// Settings of the ICMP rate limiting heuristic, see
+/// [`PingStatistics::likely_rate_limited`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitHeuristic {
+    /// Replies that have to arrive before the first loss. Defaults to 2.
+    pub min_leading_replies: usize,
+    /// Fraction of the probes after the first loss that have to be lost.
+    /// Defaults to 0.5.
+    pub min_loss_after: f64,
+}
+
+impl Default for RateLimitHeuristic {
+    fn default() -> Self {
+        RateLimitHeuristic {
+            min_leading_replies: 2,
+            min_loss_after: 0.5,
+        }
+    }
+}
+
+impl RateLimitHeuristic {
+    fn matches(&self, replied: &[bool]) -> bool {
+        let leading = replied.iter().take_while(|replied| **replied).count();
+        if leading < self.min_leading_replies || leading == replied.len() {
+            return false;
+        }
+        let rest = &replied[leading..];
+        let lost = rest.iter().filter(|replied| !**replied).count();
+        // the host still answering after the losses tells rate limiting apart
+        // from the host or path going down
+        let recovered = rest.iter().any(|replied| *replied);
+        recovered && lost as f64 / rest.len() as f64 >= self.min_loss_after
+    }
+}
+
+/// Parameters of [`PingStatistics::quality_score_with`], defaulting to the
+/// values of the common simplified E-model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityModel {
+    /// Weight of the jitter in the effective latency. Defaults to 2.
+    pub jitter_weight: f64,
+    /// Milliseconds added to the effective latency for codec delay. Defaults to 10.
+    pub codec_delay_ms: f64,
+    /// R factor points lost per percent of packet loss. Defaults to 2.5.
+    pub loss_penalty: f64,
+}
+
+impl Default for QualityModel {
+    fn default() -> Self {
+        QualityModel {
+            jitter_weight: 2.0,
+            codec_delay_ms: 10.0,
+            loss_penalty: 2.5,
+        }
+    }
+}
+
+/// Stopping rule of [`Ping::burst_until_confident`](crate::Ping::burst_until_confident).
+///
+/// The confidence interval of the mean RTT is estimated as `mean ± z * s / sqrt(n)`
+/// over the `n` replies so far, with `s` their sample standard deviation. This
+/// assumes the RTTs are independent and identically distributed and that `n` is
+/// large enough for their mean to be about normally distributed. RTTs are usually
+/// skewed by queueing and correlated in time, so the interval is an approximation,
+/// and with few replies it comes out too narrow, which `min_replies` guards
+/// against. Lost probes are not part of the interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceTarget {
+    /// Half width the interval has to shrink to. Defaults to 1 ms.
+    pub half_width: Duration,
+    /// Quantile of the standard normal distribution for the confidence level.
+    /// Defaults to 1.96, a 95% confidence level. A negative or non-finite value
+    /// never lets the interval count as narrow enough.
+    pub z: f64,
+    /// Replies needed before the interval is trusted. Defaults to 5.
+    pub min_replies: usize,
+    /// Probes sent at most, whether or not the interval got narrow enough.
+    /// Defaults to 100.
+    pub max_probes: usize,
+}
+
+impl Default for ConfidenceTarget {
+    fn default() -> Self {
+        ConfidenceTarget {
+            half_width: Duration::from_millis(1),
+            z: 1.96,
+            min_replies: 5,
+            max_probes: 100,
+        }
+    }
+}
+
+/// Running mean and variance of RTTs, using Welford's algorithm.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RttAccumulator {
+    count: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl RttAccumulator {
+    pub(crate) fn push(&mut self, rtt: Duration) {
+        let rtt = rtt.as_secs_f64();
+        self.count += 1;
+        let delta = rtt - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (rtt - self.mean);
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Sample standard deviation in seconds.
+    fn stddev(&self) -> Option<f64> {
+        if self.count < 2 {
+            return None;
+        }
+        Some((self.m2 / (self.count - 1) as f64).sqrt())
+    }
+
+    /// Half width of the confidence interval of the mean for the quantile `z`, or
+    /// `None` if `z` is negative or not finite.
+    pub(crate) fn half_width(&self, z: f64) -> Option<Duration> {
+        let stddev = self.stddev()?;
+        if !z.is_finite() || z < 0.0 {
+            return None;
+        }
+        Duration::try_from_secs_f64(z * stddev / (self.count as f64).sqrt()).ok()
+    }
+}
+
+/// How a host is doing according to [`PingStatistics::health_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Up,
+    /// Answering, but slower or lossier than the thresholds allow.
+    Degraded,
+    Down,
+}
+
+/// Thresholds of [`PingStatistics::health_with`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// Average RTT above which the host is degraded. Defaults to 100 ms.
+    pub degraded_rtt: Duration,
+    /// Loss fraction above which the host is degraded. Defaults to 0.05.
+    pub degraded_loss: f64,
+    /// Loss fraction at or above which the host is down. Defaults to 1, so a
+    /// host is only down if no probe was answered.
+    pub down_loss: f64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        HealthThresholds {
+            degraded_rtt: Duration::from_millis(100),
+            degraded_loss: 0.05,
+            down_loss: 1.0,
+        }
+    }
+}
+
+/// Summary of a series of pings, in the order they were sent. The RTTs are those
+/// of the replies, so they are rounded if the pings had an
+/// [`rtt_resolution`](crate::Ping::rtt_resolution).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingStatistics {
+    pub transmitted: usize,
+    pub received: usize,
+    /// Fraction of probes without reply, between 0 and 1.
+    pub loss: f64,
+    pub min_rtt: Option<Duration>,
+    pub avg_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    /// Mean absolute difference between the RTTs of consecutive replies.
+    pub jitter: Option<Duration>,
+    /// Sample standard deviation of the RTTs.
+    pub rtt_stddev: Option<Duration>,
+    /// Whether the losses look like the target rate limits ICMP: a few replies,
+    /// then mostly losses while some replies still get through. A token bucket
+    /// limiter lets the first probes of a burst through and afterwards only as many
+    /// as it refills, which produces this pattern; a host or link going down
+    /// loses everything after some point instead. Short bursts and genuinely lossy
+    /// links can still trigger it.
+    pub likely_rate_limited: bool,
+    /// Packets the local kernel dropped during the pings: the difference of the
+    /// [`DropCounters`](crate::DropCounters) before and after them, plus the
+    /// packets the pings' own sockets dropped because their receive buffer was
+    /// full. Set by [`Ping::burst`](crate::Ping::burst) where the counters are
+    /// available, `None` otherwise. Since the counters are system wide, drops of
+    /// other traffic are included; a count of zero rules out local drops as the
+    /// cause of the loss.
+    pub local_drops: Option<u64>,
+}
+
+impl PingStatistics {
+    /// Computes statistics using the default [`RateLimitHeuristic`].
+    pub fn from_results(results: &[Result<PingReply, Error>]) -> Self {
+        Self::from_results_with(results, Some(&RateLimitHeuristic::default()))
+    }
+
+    /// Computes statistics, running the rate limiting heuristic only if one is
+    /// given.
+    pub fn from_results_with(
+        results: &[Result<PingReply, Error>],
+        rate_limit: Option<&RateLimitHeuristic>,
+    ) -> Self {
+        let rtts: Vec<Duration> = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|reply| reply.rtt)
+            .collect();
+
+        let transmitted = results.len();
+        let received = rtts.len();
+        let loss = if transmitted == 0 {
+            0.0
+        } else {
+            (transmitted - received) as f64 / transmitted as f64
+        };
+
+        let avg_rtt = if rtts.is_empty() {
+            None
+        } else {
+            Some(rtts.iter().sum::<Duration>() / rtts.len() as u32)
+        };
+        let jitter = if rtts.len() < 2 {
+            None
+        } else {
+            let deltas: Duration = rtts.windows(2).map(|pair| pair[0].abs_diff(pair[1])).sum();
+            Some(deltas / (rtts.len() - 1) as u32)
+        };
+
+        let mut accumulator = RttAccumulator::default();
+        for rtt in &rtts {
+            accumulator.push(*rtt);
+        }
+
+        let replied: Vec<bool> = results.iter().map(Result::is_ok).collect();
+        let likely_rate_limited = rate_limit.is_some_and(|rate_limit| rate_limit.matches(&replied));
+
+        PingStatistics {
+            transmitted,
+            received,
+            loss,
+            min_rtt: rtts.iter().min().copied(),
+            avg_rtt,
+            max_rtt: rtts.iter().max().copied(),
+            jitter,
+            rtt_stddev: accumulator.stddev().map(Duration::from_secs_f64),
+            likely_rate_limited,
+            local_drops: None,
+        }
+    }
+
+    /// Estimates the quality of a voice call over the path as a mean opinion
+    /// score between 1 (bad) and 4.5 (best), using the default [`QualityModel`].
+    pub fn quality_score(&self) -> f64 {
+        self.quality_score_with(&QualityModel::default())
+    }
+
+    /// Estimates a mean opinion score with a simplified E-model:
+    ///
+    /// ```text
+    /// latency = avg_rtt + jitter_weight * jitter + codec_delay    (in ms)
+    /// R = 93.2 - latency / 40            if latency < 160
+    /// R = 93.2 - (latency - 120) / 10    otherwise
+    /// R = R - loss_penalty * loss_percent, clamped to 0..=100
+    /// MOS = 1 + 0.035 * R + 0.000007 * R * (R - 60) * (100 - R)
+    /// ```
+    ///
+    /// Without any reply the score is 1. A missing jitter counts as zero.
+    pub fn quality_score_with(&self, model: &QualityModel) -> f64 {
+        let avg_rtt = match self.avg_rtt {
+            Some(avg_rtt) => avg_rtt.as_secs_f64() * 1000.0,
+            None => return 1.0,
+        };
+        let jitter = self.jitter.unwrap_or_default().as_secs_f64() * 1000.0;
+
+        let latency = avg_rtt + model.jitter_weight * jitter + model.codec_delay_ms;
+        let r = if latency < 160.0 {
+            93.2 - latency / 40.0
+        } else {
+            93.2 - (latency - 120.0) / 10.0
+        };
+        let r = (r - model.loss_penalty * self.loss * 100.0).clamp(0.0, 100.0);
+        1.0 + 0.035 * r + 0.000007 * r * (r - 60.0) * (100.0 - r)
+    }
+
+    /// Half width of the confidence interval of the mean RTT for the standard
+    /// normal quantile `z`, e.g. 1.96 for 95%, under the assumptions described at
+    /// [`ConfidenceTarget`]. `None` with fewer than two replies or a negative or
+    /// non-finite `z`.
+    pub fn rtt_confidence_half_width(&self, z: f64) -> Option<Duration> {
+        let stddev = self.rtt_stddev?.as_secs_f64();
+        if !z.is_finite() || z < 0.0 {
+            return None;
+        }
+        Duration::try_from_secs_f64(z * stddev / (self.received as f64).sqrt()).ok()
+    }
+
+    /// Classifies the host using the default [`HealthThresholds`].
+    pub fn health(&self) -> HealthStatus {
+        self.health_with(&HealthThresholds::default())
+    }
+
+    /// Classifies the host as down if the loss reaches `down_loss` or nothing was
+    /// answered, as degraded if the loss exceeds `degraded_loss` or the average RTT
+    /// exceeds `degraded_rtt`, and as up otherwise. Without any probe the host is
+    /// down.
+    pub fn health_with(&self, thresholds: &HealthThresholds) -> HealthStatus {
+        let avg_rtt = match self.avg_rtt {
+            Some(avg_rtt) if self.loss < thresholds.down_loss => avg_rtt,
+            _ => return HealthStatus::Down,
+        };
+        if self.loss > thresholds.degraded_loss || avg_rtt > thresholds.degraded_rtt {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Up
+        }
+    }
+}
+
+/// The results of [`Ping::burst`](crate::Ping::burst).
+#[derive(Debug)]
+pub struct Burst {
+    pub results: Vec<Result<PingReply, Error>>,
+    pub statistics: PingStatistics,
+    /// The health of the target according to the statistics and the
+    /// [`health_thresholds`](crate::Ping::health_thresholds) of the ping.
+    pub health: HealthStatus,
+}
+
+impl Burst {
+    /// The times between the arrivals of consecutive replies, from their
+    /// [`received_at`](PingReply::received_at), one less than there are replies.
+    /// Unlike the RTT, which is measured from each probe's own send, and the
+    /// jitter, which averages the differences of those RTTs, they show how the
+    /// path spaces the replies out: steady RTTs keep them close to the send
+    /// interval, while replies held back and released together arrive in clusters
+    /// with short gaps. The gap around a lost probe spans it. Computed on each call,
+    /// so bursts that never ask pay nothing for them.
+    pub fn inter_arrival_times(&self) -> Vec<Duration> {
+        let mut received: Vec<_> = self
+            .results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .map(|reply| reply.received_at)
+            .collect();
+        received.sort();
+        received
+            .windows(2)
+            .map(|pair| pair[1].duration_since(pair[0]))
+            .collect()
+    }
+}
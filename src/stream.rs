@@ -0,0 +1,136 @@
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{random, RngCore, SeedableRng};
+
+use crate::cancel::{self, CancelToken};
+use crate::errors::Error;
+use crate::ping::{Ping, PingReply, TOKEN_SIZE};
+use crate::record::{EventKind, Recorder};
+
+/// An endless series of pings to the same target, one per interval.
+///
+/// Each item is the outcome of one probe. Transient errors (see
+/// [`Error::is_transient`]) such as timeouts or send failures during a link flap
+/// are yielded and the stream carries on at the next interval; any other error is
+/// yielded once and ends the stream. Errors are yielded as they occurred, so e.g.
+/// a failed send keeps its OS error number, see [`Error::raw_os_error`].
+///
+/// Unless the ping has a fixed payload, every probe carries a fresh random token
+/// and a reply is only accepted if it echoes the token of its own probe, so late
+/// or duplicated replies to earlier probes are told apart by payload as well as
+/// by sequence number. Probes are sent one at a time, so only the token of the
+/// probe in flight is kept.
+#[derive(Debug)]
+pub struct PingStream<'a> {
+    ping: Ping<'a>,
+    interval: Duration,
+    seq_cnt: u16,
+    next_send: Instant,
+    finished: bool,
+    recorder: Option<Recorder>,
+    rng: StreamRng,
+}
+
+struct StreamRng(Box<dyn RngCore + Send>);
+
+impl fmt::Debug for StreamRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StreamRng")
+    }
+}
+
+impl<'a> PingStream<'a> {
+    pub(crate) fn new(ping: Ping<'a>, interval: Duration) -> Self {
+        let seq_cnt = ping.seq_cnt.unwrap_or(1);
+        // all probes of a stream share one ident
+        let ident = ping.ident.unwrap_or_else(random);
+        PingStream {
+            ping: ping.ident(ident),
+            interval,
+            seq_cnt,
+            next_send: Instant::now(),
+            finished: false,
+            recorder: None,
+            rng: StreamRng(Box::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// Sets the random number generator the per probe tokens are drawn from, e.g.
+    /// a seeded one for reproducible packet captures.
+    pub fn rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = StreamRng(Box::new(rng));
+        self
+    }
+
+    /// Records every sent probe and its outcome, keeping the last `capacity`
+    /// events, see [`Recorder`].
+    pub fn record(mut self, capacity: usize) -> Self {
+        self.recorder = Some(Recorder::new(capacity));
+        self
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+}
+
+impl Iterator for PingStream<'_> {
+    type Item = Result<PingReply, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            if self
+                .ping
+                .cancel
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+            {
+                self.finished = true;
+                return None;
+            }
+            let now = Instant::now();
+            if self.next_send <= now {
+                break;
+            }
+            let wait = self.next_send - now;
+            if self.ping.cancel.is_some() {
+                thread::sleep(wait.min(cancel::POLL_INTERVAL));
+            } else {
+                thread::sleep(wait);
+            }
+        }
+        self.next_send += self.interval;
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.seq_cnt, EventKind::Sent);
+        }
+        let mut ping = self.ping.clone().seq_cnt(self.seq_cnt).verify_payload(true);
+        if ping.payload.is_none() {
+            let mut token = vec![0; ping.payload_size.unwrap_or(TOKEN_SIZE)];
+            self.rng.0.fill_bytes(&mut token);
+            ping.token = Some(token);
+        }
+        let result = ping.ping();
+        // a cancelled probe is recorded as well, so its sent event does not dangle
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.seq_cnt, EventKind::from_result(&result));
+        }
+        if let Err(Error::Cancelled) = result {
+            self.finished = true;
+            return None;
+        }
+        self.seq_cnt = self.seq_cnt.wrapping_add(1);
+
+        if let Err(error) = &result {
+            self.finished = !error.is_transient();
+        }
+        Some(result)
+    }
+}
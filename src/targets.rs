@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+
+use crate::errors::Error;
+
+const DEFAULT_MAX_ADDRS: usize = 65536;
+
+/// Turns a mixed list of addresses, hostnames and CIDR ranges into the addresses
+/// to pass to [`ping_many`](crate::ping_many).
+///
+/// Hostnames are resolved to all their addresses, ranges like `192.0.2.0/24` are
+/// expanded to every address they contain, including the network and broadcast
+/// addresses, and duplicates are removed, keeping the first occurrence.
+#[derive(Debug, Clone)]
+pub struct TargetSet {
+    inputs: Vec<String>,
+    max_addrs: usize,
+}
+
+/// The result of [`TargetSet::build`].
+#[derive(Debug)]
+pub struct Targets {
+    pub addrs: Vec<IpAddr>,
+    /// The inputs that did not yield any address, with the reason.
+    pub errors: Vec<(String, Error)>,
+}
+
+impl TargetSet {
+    pub fn new() -> Self {
+        TargetSet {
+            inputs: Vec::new(),
+            max_addrs: DEFAULT_MAX_ADDRS,
+        }
+    }
+
+    pub fn target(mut self, input: impl Into<String>) -> Self {
+        self.inputs.push(input.into());
+        self
+    }
+
+    pub fn targets<S: Into<String>>(mut self, inputs: impl IntoIterator<Item = S>) -> Self {
+        self.inputs.extend(inputs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Caps the number of addresses, so a typo like `/8` instead of `/24` does not
+    /// start a sweep of millions of hosts. A range or hostname that would exceed
+    /// the cap is skipped as a whole and reported with
+    /// [`Error::TooManyTargets`]. Defaults to 65536.
+    pub fn max_addrs(mut self, max_addrs: usize) -> Self {
+        self.max_addrs = max_addrs;
+        self
+    }
+
+    /// Parses, resolves and expands every input in order. Hostnames are resolved
+    /// with the system resolver, which blocks.
+    pub fn build(&self) -> Targets {
+        let mut seen = HashSet::new();
+        let mut targets = Targets {
+            addrs: Vec::new(),
+            errors: Vec::new(),
+        };
+        for input in &self.inputs {
+            let remaining = self.max_addrs - targets.addrs.len();
+            match expand(input.trim(), remaining) {
+                Ok(addrs) => {
+                    for addr in addrs {
+                        if seen.insert(addr) {
+                            targets.addrs.push(addr);
+                        }
+                    }
+                }
+                Err(error) => targets.errors.push((input.clone(), error)),
+            }
+        }
+        targets
+    }
+}
+
+impl Default for TargetSet {
+    fn default() -> Self {
+        TargetSet::new()
+    }
+}
+
+/// The `count` addresses from `network` on. `count` is a u128 since a /0 holds
+/// 2^32 addresses, one more than a u32 can count.
+fn v4_range(network: u32, count: u128) -> impl DoubleEndedIterator<Item = IpAddr> {
+    (0..count).map(move |offset| IpAddr::V4(Ipv4Addr::from(network + offset as u32)))
+}
+
+fn expand(input: &str, remaining: usize) -> Result<Vec<IpAddr>, Error> {
+    let invalid = || Error::InvalidTarget {
+        input: input.to_string(),
+    };
+    let too_many = || Error::TooManyTargets { limit: remaining };
+
+    if let Some((addr, prefix)) = input.split_once('/') {
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let prefix: u32 = prefix.parse().map_err(|_| invalid())?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > bits {
+            return Err(invalid());
+        }
+        let count = 1u128.checked_shl(bits - prefix).ok_or_else(too_many)?;
+        if count > remaining as u128 {
+            return Err(too_many());
+        }
+        let addrs = match addr {
+            IpAddr::V4(addr) => {
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                v4_range(u32::from(addr) & mask, count).collect()
+            }
+            IpAddr::V6(addr) => {
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                let network = u128::from(addr) & mask;
+                (0..count)
+                    .map(|offset| IpAddr::V6(Ipv6Addr::from(network + offset)))
+                    .collect()
+            }
+        };
+        return Ok(addrs);
+    }
+
+    if let Ok(addr) = input.parse() {
+        return Ok(vec![addr]);
+    }
+    if input.is_empty() {
+        return Err(invalid());
+    }
+    let mut addrs: Vec<IpAddr> = (input, 0)
+        .to_socket_addrs()?
+        .map(|addr| addr.ip())
+        .collect();
+    addrs.dedup();
+    if addrs.len() > remaining {
+        return Err(too_many());
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4_range_covers_whole_space() {
+        let mut range = v4_range(0, 1 << 32);
+        assert_eq!(range.size_hint(), (1 << 32, Some(1 << 32)));
+        assert_eq!(range.next(), Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+        assert_eq!(range.next_back(), Some(IpAddr::V4(Ipv4Addr::BROADCAST)));
+    }
+}
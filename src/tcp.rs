@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SCAN_CONCURRENCY: usize = 16;
+
+/// The state of a TCP port as seen by [`tcp_scan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortState {
+    /// The connection was accepted after the given time.
+    Open(Duration),
+    /// The connection was refused after the given time.
+    Closed(Duration),
+    /// No answer before the timeout, or the connection failed otherwise.
+    Filtered,
+}
+
+/// Connects to each of `ports` on `addr` and reports which accept connections,
+/// along with the time the connect took. Useful where ICMP is blocked but
+/// services are reachable.
+///
+/// At most 16 connections are attempted at the same time, each on its own
+/// thread, and every attempt gives up after `timeout`.
+pub fn tcp_scan(addr: IpAddr, ports: &[u16], timeout: Duration) -> BTreeMap<u16, PortState> {
+    let next = AtomicUsize::new(0);
+    let states = Mutex::new(BTreeMap::new());
+
+    thread::scope(|scope| {
+        for _ in 0..SCAN_CONCURRENCY.min(ports.len()) {
+            scope.spawn(|| loop {
+                let port = match ports.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    Some(port) => *port,
+                    None => return,
+                };
+                let state = probe(SocketAddr::new(addr, port), timeout);
+                if let Ok(mut states) = states.lock() {
+                    states.insert(port, state);
+                }
+            });
+        }
+    });
+
+    states.into_inner().unwrap_or_default()
+}
+
+fn probe(addr: SocketAddr, timeout: Duration) -> PortState {
+    let start = Instant::now();
+    match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => PortState::Open(start.elapsed()),
+        Err(error) if error.kind() == ErrorKind::ConnectionRefused => {
+            PortState::Closed(start.elapsed())
+        }
+        Err(_) => PortState::Filtered,
+    }
+}
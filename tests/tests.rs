@@ -1,10 +1,44 @@
 extern crate ping;
 extern crate rand;
 
+use std::net::IpAddr;
 use std::time::Duration;
 
 use rand::random;
 
+/// The non-loopback address the host sends from towards `remote`, found by
+/// connecting a UDP socket, which sends nothing. `None` without such a route.
+fn local_addr_towards(remote: &str) -> Option<IpAddr> {
+    let unspecified = if remote.starts_with('[') {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = std::net::UdpSocket::bind(unspecified).ok()?;
+    socket.connect(remote).ok()?;
+    let local = socket.local_addr().ok()?.ip();
+    if local.is_loopback() || local.is_unspecified() {
+        None
+    } else {
+        Some(local)
+    }
+}
+
+/// A reply as a ping would return it, with only the RTT of interest.
+fn fake_reply(rtt: Duration) -> ping::PingReply {
+    ping::PingReply {
+        ident: 1,
+        seq_cnt: 1,
+        rtt,
+        ttl: None,
+        dont_fragment: None,
+        checksum_valid: true,
+        send_wallclock: None,
+        received_at: std::time::Instant::now(),
+        setup_time: None,
+    }
+}
+
 #[test]
 fn basic() {
     let addr = "127.0.0.1".parse().unwrap();
@@ -39,6 +73,25 @@ fn basic_v6() {
     .unwrap();
 }
 
+#[test]
+fn nd_warmup() {
+    let warmup = Duration::from_millis(300);
+    // only link-local targets wait, whether or not they answer
+    let start = std::time::Instant::now();
+    let _ = ping::Ping::new("fe80::1".parse().unwrap())
+        .timeout(Duration::from_millis(100))
+        .nd_warmup(warmup)
+        .ping();
+    assert!(start.elapsed() >= warmup);
+
+    let start = std::time::Instant::now();
+    ping::Ping::new("::1".parse().unwrap())
+        .nd_warmup(Duration::from_secs(2))
+        .ping()
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
 #[cfg(not(target_os = "windows"))]
 #[test]
 fn basic_dgram() {
@@ -81,3 +134,1264 @@ fn builder_api() {
     let timeout = Duration::from_secs(1);
     ping::Ping::new(addr).timeout(timeout).ping().unwrap();
 }
+
+#[test]
+fn pool_api() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let pool = ping::PingPool::new(2);
+    let receivers: Vec<_> = (0..4)
+        .map(|_| pool.submit(ping::Ping::new(addr).timeout(timeout)))
+        .collect();
+    for receiver in receivers {
+        receiver.recv().unwrap().unwrap();
+    }
+}
+
+#[test]
+fn iputils_payload() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    ping::Ping::new(addr)
+        .timeout(timeout)
+        .iputils_payload(true)
+        .ping()
+        .unwrap();
+}
+
+#[test]
+fn stream_api() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let replies: Vec<_> = ping::Ping::new(addr)
+        .timeout(timeout)
+        .seq_cnt(7)
+        .stream(Duration::from_millis(10))
+        .take(3)
+        .map(Result::unwrap)
+        .collect();
+    let seq_cnts: Vec<_> = replies.iter().map(|reply| reply.seq_cnt).collect();
+    assert_eq!(seq_cnts, [7, 8, 9]);
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn nonblocking_socket() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let socket = ping::PingSocket::new(ping::Type::DGRAM);
+    socket.set_nonblocking(true).unwrap();
+    socket.send(addr, 3, 5, &random::<[u8; 24]>()).unwrap();
+
+    let start = std::time::Instant::now();
+    let reply = loop {
+        match socket.recv(addr) {
+            Err(ping::Error::WouldBlock) if start.elapsed() < Duration::from_secs(1) => {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            result => break result.unwrap(),
+        }
+    };
+    assert_eq!(reply.seq_cnt, 5);
+    assert!(matches!(socket.recv(addr), Err(ping::Error::WouldBlock)));
+
+    // a ping waits for its reply anyway and leaves the socket non-blocking
+    let reply = socket
+        .ping(ping::Ping::new(addr).timeout(Duration::from_secs(1)))
+        .unwrap();
+    assert!(!reply.rtt.is_zero());
+    assert!(matches!(socket.recv(addr), Err(ping::Error::WouldBlock)));
+
+    // waiting for a reply that never comes sleeps instead of spinning
+    #[cfg(target_os = "linux")]
+    {
+        // utime and stime of this thread, in clock ticks of usually 10 ms
+        let cpu_ticks = || -> u64 {
+            let stat = std::fs::read_to_string("/proc/thread-self/stat").unwrap();
+            let fields: Vec<&str> = stat.rsplit(')').next().unwrap().split(' ').collect();
+            fields[12].parse::<u64>().unwrap() + fields[13].parse::<u64>().unwrap()
+        };
+        let before = cpu_ticks();
+        let result = socket.ping(
+            ping::Ping::new("192.0.2.99".parse().unwrap()).timeout(Duration::from_millis(300)),
+        );
+        assert!(result.unwrap_err().is_timeout());
+        assert!(cpu_ticks() - before < 10);
+    }
+}
+
+#[test]
+fn path_symmetry_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let path = ping::path_symmetry(addr, Duration::from_secs(1)).unwrap();
+    assert_eq!(path.forward_hops, 1);
+    assert_eq!(path.return_hops, 1);
+    assert!(!path.asymmetric);
+}
+
+#[test]
+fn batch_api() {
+    let addrs = ["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+    let timeout = Duration::from_secs(1);
+    let outcomes = ping::Batch::new()
+        .configure(|ping| ping.timeout(timeout))
+        .ping_many(&addrs);
+    assert_eq!(outcomes.len(), 2);
+    for outcome in outcomes {
+        outcome.into_result().unwrap();
+    }
+}
+
+#[test]
+fn raw_icmp_echo() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let socket = ping::PingSocket::new(ping::Type::RAW);
+    socket
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let mut body = vec![0, 3, 0, 5];
+    body.extend_from_slice(&random::<[u8; 24]>());
+    let request = ping::RawIcmp {
+        icmp_type: 8,
+        icmp_code: 0,
+        body,
+    };
+    socket.send_raw(addr, &request).unwrap();
+    loop {
+        let reply = socket.recv_raw(addr).unwrap();
+        if reply.icmp_type == 0 && reply.body == request.body {
+            break;
+        }
+    }
+}
+
+#[test]
+fn ident_allocator_skips_in_flight() {
+    let mut allocator = ping::IdentAllocator::with_range(10..=12);
+    assert_eq!(allocator.allocate(1), Some(10));
+    assert_eq!(allocator.allocate(1), Some(11));
+    allocator.release(10, 1);
+    assert_eq!(allocator.allocate(1), Some(12));
+    // wraps around and skips 11, which is still in flight with seq 1
+    assert_eq!(allocator.allocate(1), Some(10));
+    assert_eq!(allocator.allocate(1), None);
+    // the same idents are free for another seq
+    assert_eq!(allocator.allocate(2), Some(11));
+    assert_eq!(allocator.in_flight(), 4);
+}
+
+#[test]
+fn stream_recorder() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let mut stream = ping::Ping::new(addr)
+        .timeout(timeout)
+        .stream(Duration::from_millis(10))
+        .record(3);
+    stream.by_ref().take(2).for_each(|result| {
+        result.unwrap();
+    });
+
+    let recorder = stream.recorder().unwrap();
+    assert_eq!(recorder.events().count(), 3);
+    assert_eq!(recorder.dropped(), 1);
+
+    let mut csv = Vec::new();
+    recorder.export_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), 4);
+    assert!(csv.lines().nth(1).unwrap().contains(",reply,"));
+}
+
+#[cfg(not(target_os = "windows"))]
+#[test]
+fn connected_dgram() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    ping::Ping::new(addr)
+        .socket_type(ping::Type::DGRAM)
+        .timeout(timeout)
+        .connect(true)
+        .ping()
+        .unwrap();
+}
+
+#[test]
+fn burst_statistics() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let burst = ping::Ping::new(addr)
+        .timeout(timeout)
+        .burst(3, Duration::from_millis(10));
+    assert_eq!(burst.results.len(), 3);
+    assert_eq!(burst.statistics.transmitted, 3);
+    assert_eq!(burst.statistics.received, 3);
+    assert_eq!(burst.statistics.loss, 0.0);
+    assert!(burst.statistics.jitter.is_some());
+    assert!(!burst.statistics.likely_rate_limited);
+    assert!(burst.statistics.quality_score() > 4.0);
+    // the drop counters are only available on Linux
+    if cfg!(target_os = "linux") {
+        assert!(burst.statistics.local_drops.is_some());
+    }
+}
+
+#[test]
+fn rate_limit_heuristic() {
+    let timeout = || {
+        let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout");
+        Err(ping::Error::from(error))
+    };
+    let reply = || Ok(fake_reply(Duration::from_millis(1)));
+
+    let limited = [
+        reply(),
+        reply(),
+        reply(),
+        timeout(),
+        timeout(),
+        reply(),
+        timeout(),
+    ];
+    assert!(ping::PingStatistics::from_results(&limited).likely_rate_limited);
+    assert!(!ping::PingStatistics::from_results_with(&limited, None).likely_rate_limited);
+
+    let down = [reply(), reply(), reply(), timeout(), timeout(), timeout()];
+    assert!(!ping::PingStatistics::from_results(&down).likely_rate_limited);
+}
+
+#[test]
+fn reused_dual_stack_socket() {
+    let v4 = "127.0.0.1".parse().unwrap();
+    let v6 = "::1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let socket = ping::PingSocket::new(ping::Type::RAW);
+    for addr in [v4, v6, v4, v6] {
+        socket.ping(ping::Ping::new(addr).timeout(timeout)).unwrap();
+    }
+}
+
+#[test]
+fn first_hop_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let hop = ping::first_hop(addr, Duration::from_secs(1)).unwrap();
+    assert_eq!(hop.addr, addr);
+}
+
+#[test]
+fn pmtu_blackhole_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let verdict = ping::detect_pmtu_blackhole(addr, 1500, Duration::from_secs(1)).unwrap();
+    assert_eq!(verdict, ping::PmtuVerdict::Passes);
+
+    let reply = ping::Ping::new(addr)
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_secs(1))
+        .payload_size(1000)
+        .dont_fragment(true)
+        .ping()
+        .unwrap();
+    assert!(reply.dont_fragment.is_some());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn dont_fragment_per_ping() {
+    // larger than the MTU of any link but loopback, so only DF fails to send it
+    let addr = "192.0.2.99".parse().unwrap();
+    let socket = ping::PingSocket::new(ping::Type::DGRAM);
+    let ping = ping::Ping::new(addr)
+        .timeout(Duration::from_millis(100))
+        .payload_size(65000);
+    let result = socket.ping(ping.clone().dont_fragment(true));
+    assert!(!result.unwrap_err().is_timeout());
+    // the next ping on the same socket may be fragmented again
+    let result = socket.ping(ping);
+    assert!(result.unwrap_err().is_timeout());
+}
+
+#[test]
+fn reply_checksum_valid() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let reply = ping::Ping::new(addr)
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_secs(1))
+        .verify_checksum(true)
+        .ping()
+        .unwrap();
+    assert!(reply.checksum_valid);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn ipv6_extension_headers() {
+    let addr = "::1".parse().unwrap();
+    // next header, length, PadN option with 4 bytes of padding
+    let options = [0, 0, 1, 4, 0, 0, 0, 0];
+    ping::Ping::new(addr)
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_secs(1))
+        .ipv6_hop_by_hop_options(&options)
+        .ipv6_destination_options(&options)
+        .ping()
+        .unwrap();
+
+    let result = ping::Ping::new(addr)
+        .socket_type(ping::Type::DGRAM)
+        .ipv6_hop_by_hop_options(&options)
+        .ping();
+    assert!(matches!(result, Err(ping::Error::InvalidProtocol)));
+}
+
+#[test]
+fn stream_with_seeded_rng() {
+    use rand::SeedableRng;
+
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let stream = ping::Ping::new(addr)
+        .timeout(timeout)
+        .stream(Duration::from_millis(10))
+        .rng(rand::rngs::StdRng::seed_from_u64(7));
+    for result in stream.take(3) {
+        result.unwrap();
+    }
+}
+
+#[test]
+fn max_iterations_exceeded() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let result = ping::Ping::new(addr).max_iterations(0).ping();
+    assert!(matches!(result, Err(ping::Error::TooManyForeignPackets)));
+}
+
+#[test]
+fn custom_icmp_message() {
+    struct Echo(u16);
+
+    struct Reply(u16);
+
+    impl ping::IcmpMessage for Echo {
+        type Reply = Reply;
+
+        fn encoded_len(&self) -> usize {
+            8
+        }
+
+        fn encode(
+            &self,
+            version: ping::IcmpVersion,
+            buffer: &mut [u8],
+        ) -> Result<usize, ping::Error> {
+            buffer[0] = match version {
+                ping::IcmpVersion::V4 => 8,
+                ping::IcmpVersion::V6 => 128,
+            };
+            buffer[4..6].copy_from_slice(&self.0.to_be_bytes());
+            ping::write_checksum(&mut buffer[..8]);
+            Ok(8)
+        }
+    }
+
+    impl ping::IcmpReply for Reply {
+        fn decode(version: ping::IcmpVersion, buffer: &[u8]) -> Result<Self, ping::Error> {
+            let reply_type = match version {
+                ping::IcmpVersion::V4 => 0,
+                ping::IcmpVersion::V6 => 129,
+            };
+            if buffer.len() < 8 || buffer[0] != reply_type {
+                return Err(ping::Error::DecodeEchoReplyError);
+            }
+            Ok(Reply(u16::from_be_bytes([buffer[4], buffer[5]])))
+        }
+    }
+
+    let addr = "127.0.0.1".parse().unwrap();
+    let socket = ping::PingSocket::new(ping::Type::RAW);
+    socket
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let ident = random();
+    loop {
+        let reply = socket.request(addr, &Echo(ident)).unwrap();
+        if reply.0 == ident {
+            break;
+        }
+    }
+}
+
+#[test]
+fn sort_batch_by_rtt() {
+    let reply = |millis| ping::PingOutcome::Reply(fake_reply(Duration::from_millis(millis)));
+    let addrs: Vec<std::net::IpAddr> = ["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4"]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+    let outcomes = vec![
+        ping::PingOutcome::NoReply,
+        reply(20),
+        ping::PingOutcome::Error(ping::Error::InternalError),
+        reply(10),
+    ];
+    let sorted: Vec<_> = ping::sort_by_rtt(&addrs, outcomes)
+        .into_iter()
+        .map(|(addr, _)| addr)
+        .collect();
+    assert_eq!(sorted, [addrs[3], addrs[1], addrs[0], addrs[2]]);
+}
+
+#[test]
+fn batch_slot_idents() {
+    let addrs = vec!["127.0.0.1".parse().unwrap(); 4];
+    let timeout = Duration::from_secs(1);
+    let outcomes = ping::Batch::new()
+        .configure(|ping| ping.socket_type(ping::Type::RAW).timeout(timeout))
+        .concurrency(2)
+        .slot_idents(4000)
+        .ping_many(&addrs);
+    for outcome in outcomes {
+        let ident = outcome.into_result().unwrap().ident;
+        assert!(ident == 4000 || ident == 4001);
+    }
+
+    // DGRAM sockets send the local port as ident on Linux instead
+    if cfg!(target_os = "linux") {
+        let outcomes = ping::Batch::new()
+            .configure(|ping| ping.socket_type(ping::Type::DGRAM).timeout(timeout))
+            .slot_idents(4000)
+            .ping_many(&addrs[..1]);
+        assert!(matches!(
+            outcomes[0],
+            ping::PingOutcome::Error(ping::Error::InvalidProtocol)
+        ));
+    }
+}
+
+#[test]
+fn tcp_scan_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let open = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let open_port = open.local_addr().unwrap().port();
+    let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let states = ping::tcp_scan(addr, &[open_port, closed_port], Duration::from_secs(1));
+    assert!(matches!(states[&open_port], ping::PortState::Open(_)));
+    assert!(matches!(states[&closed_port], ping::PortState::Closed(_)));
+}
+
+/// The interface of the IPv4 default route, from `/proc/net/route`.
+#[cfg(target_os = "linux")]
+fn default_route_iface() -> Option<String> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let iface = fields.next()?;
+        (fields.next()? == "00000000").then(|| iface.to_string())
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn bind_orders() {
+    let addr = "::1".parse().unwrap();
+    let timeout = Duration::from_millis(200);
+    for order in [ping::BindOrder::DeviceFirst, ping::BindOrder::AddressFirst] {
+        ping::Ping::new(addr)
+            .bind(addr)
+            .iface(b"lo")
+            .bind_order(order)
+            .timeout(timeout)
+            .ping()
+            .unwrap();
+    }
+
+    // binding to an address of another interface is refused once the interface is set
+    let iface = match default_route_iface() {
+        Some(iface) => iface,
+        None => return,
+    };
+    let result = ping::Ping::new(addr)
+        .bind(addr)
+        .iface(iface.as_bytes())
+        .bind_order(ping::BindOrder::DeviceFirst)
+        .timeout(timeout)
+        .ping();
+    assert!(matches!(
+        result,
+        Err(ping::Error::IoError { error }) if error.kind() == std::io::ErrorKind::AddrNotAvailable
+    ));
+    let result = ping::Ping::new(addr)
+        .bind(addr)
+        .iface(iface.as_bytes())
+        .bind_order(ping::BindOrder::AddressFirst)
+        .timeout(timeout)
+        .ping();
+    assert!(result.unwrap_err().is_timeout());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn vrf_requires_vrf_device() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let result = ping::Ping::new(addr).vrf("lo").ping();
+    assert!(matches!(
+        result,
+        Err(ping::Error::IoError { error }) if error.kind() == std::io::ErrorKind::InvalidInput
+    ));
+    let result = ping::Ping::new(addr).vrf("no-such-vrf").ping();
+    assert!(matches!(
+        result,
+        Err(ping::Error::IoError { error }) if error.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[test]
+fn rtt_resolution() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let resolution = Duration::from_millis(5);
+    let reply = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .rtt_resolution(resolution)
+        .ping()
+        .unwrap();
+    assert_eq!(reply.rtt.as_nanos() % resolution.as_nanos(), 0);
+}
+
+#[test]
+fn crate_capabilities() {
+    let capabilities = ping::crate_capabilities();
+    assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(capabilities.serde, cfg!(feature = "serde"));
+    // the other tests need both socket types
+    assert!(capabilities.raw_v4 && capabilities.dgram_v4);
+}
+
+#[test]
+fn health_status() {
+    let timeout = || {
+        let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout");
+        Err(ping::Error::from(error))
+    };
+    let reply = || Ok(fake_reply(Duration::from_millis(1)));
+
+    let up = ping::PingStatistics::from_results(&[reply(), reply(), reply(), reply()]);
+    assert_eq!(up.health(), ping::HealthStatus::Up);
+    let slow = ping::HealthThresholds {
+        degraded_rtt: Duration::ZERO,
+        ..Default::default()
+    };
+    assert_eq!(up.health_with(&slow), ping::HealthStatus::Degraded);
+
+    let lossy = ping::PingStatistics::from_results(&[reply(), reply(), reply(), timeout()]);
+    assert_eq!(lossy.health(), ping::HealthStatus::Degraded);
+
+    let down = ping::PingStatistics::from_results(&[timeout(), timeout()]);
+    assert_eq!(down.health(), ping::HealthStatus::Down);
+}
+
+#[test]
+fn loss_threshold_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let search = ping::LossThresholdSearch {
+        step: 704,
+        repeats: 2,
+        ..Default::default()
+    };
+    let threshold = ping::find_loss_threshold_with(addr, &search).unwrap();
+    assert_eq!(threshold, None);
+}
+
+#[test]
+fn cancel_stream() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let token = ping::CancelToken::new();
+    let mut stream = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .cancel_token(token.clone())
+        .stream(Duration::from_secs(10));
+    stream.next().unwrap().unwrap();
+
+    // the stream would otherwise wait 10 seconds for the next probe
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        token.cancel();
+    });
+    let start = std::time::Instant::now();
+    assert!(stream.next().is_none());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    canceller.join().unwrap();
+
+    // a ping waiting for its reply is cancelled as well
+    let token = ping::CancelToken::new();
+    let canceller = {
+        let token = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        })
+    };
+    let start = std::time::Instant::now();
+    let result = ping::Ping::new("192.0.2.123".parse().unwrap())
+        .timeout(Duration::from_secs(5))
+        .cancel_token(token)
+        .ping();
+    assert!(matches!(result, Err(ping::Error::Cancelled)));
+    assert!(start.elapsed() < Duration::from_secs(2));
+    canceller.join().unwrap();
+}
+
+#[test]
+fn target_set() {
+    let targets = ping::TargetSet::new()
+        .target("127.0.0.1")
+        .target("localhost")
+        .targets(["10.0.0.0/30", "10.0.0.1", "::1", "fd00::/127"])
+        .targets(["10.0.0.0/33", "10.0.0.0/8", "", "0.0.0.0/0"])
+        .max_addrs(16)
+        .build();
+
+    let expected: Vec<IpAddr> = ["127.0.0.1", "10.0.0.0", "10.0.0.1", "10.0.0.2", "10.0.0.3"]
+        .iter()
+        .map(|addr| addr.parse().unwrap())
+        .collect();
+    assert_eq!(targets.addrs[0], expected[0]);
+    // localhost may resolve to ::1 as well
+    let rest: Vec<IpAddr> = targets.addrs[1..]
+        .iter()
+        .copied()
+        .filter(|addr| addr.is_ipv4())
+        .collect();
+    assert_eq!(rest, expected[1..]);
+    assert_eq!(targets.addrs.len(), 8);
+    assert!(targets.addrs.contains(&"fd00::1".parse().unwrap()));
+
+    let failed: Vec<&str> = targets
+        .errors
+        .iter()
+        .map(|(input, _)| input.as_str())
+        .collect();
+    assert_eq!(failed, ["10.0.0.0/33", "10.0.0.0/8", "", "0.0.0.0/0"]);
+    assert!(matches!(
+        targets.errors[1].1,
+        ping::Error::TooManyTargets { .. }
+    ));
+    assert!(matches!(
+        targets.errors[3].1,
+        ping::Error::TooManyTargets { .. }
+    ));
+
+    // ranges are expanded in full up to the very top of the address space
+    let targets = ping::TargetSet::new()
+        .targets(["192.0.2.7/32", "255.255.255.254/31"])
+        .max_addrs(usize::MAX)
+        .build();
+    assert!(targets.errors.is_empty());
+    assert_eq!(targets.addrs.len(), 3);
+}
+
+#[test]
+fn stream_errors_keep_errno() {
+    let addr = "127.0.0.1".parse().unwrap();
+    // binding to an address that is not local fails with EADDRNOTAVAIL
+    let mut stream = ping::Ping::new(addr)
+        .bind("192.0.2.99".parse().unwrap())
+        .stream(Duration::from_millis(10))
+        .record(8);
+    let error = stream.next().unwrap().unwrap_err();
+    match &error {
+        ping::Error::IoError { error } => {
+            assert_eq!(error.kind(), std::io::ErrorKind::AddrNotAvailable)
+        }
+        error => panic!("unexpected {:?}", error),
+    }
+    let errno = error.raw_os_error();
+    assert!(errno.is_some());
+
+    let recorder = stream.recorder().unwrap();
+    assert!(recorder.events().any(|event| matches!(
+        event.kind,
+        ping::EventKind::Error { raw_os_error, .. } if raw_os_error == errno
+    )));
+}
+
+#[test]
+fn stream_ends_on_fatal_error() {
+    // a lost reply is yielded and the stream carries on
+    let mut stream = ping::Ping::new("192.0.2.99".parse().unwrap())
+        .timeout(Duration::from_millis(50))
+        .stream(Duration::from_millis(10));
+    let error = stream.next().unwrap().unwrap_err();
+    assert!(error.is_transient());
+    assert!(stream.next().is_some());
+
+    // options the socket cannot send fail every probe, so the stream ends
+    let options = [0, 0, 1, 4, 0, 0, 0, 0];
+    let mut stream = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .ipv6_hop_by_hop_options(&options)
+        .stream(Duration::from_millis(10));
+    assert!(matches!(
+        stream.next(),
+        Some(Err(ping::Error::InvalidProtocol))
+    ));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn rtt_is_never_zero() {
+    let resolution = ping::clock_resolution();
+    assert!(!resolution.is_zero());
+    assert!(resolution < Duration::from_millis(100));
+
+    let addr = "127.0.0.1".parse().unwrap();
+    let reply = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .ping()
+        .unwrap();
+    assert!(reply.rtt >= resolution);
+
+    // a loopback RTT rounds to the resolution, not down to zero
+    let reply = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .rtt_resolution(Duration::from_secs(1))
+        .ping()
+        .unwrap();
+    assert_eq!(reply.rtt, Duration::from_secs(1));
+}
+
+#[test]
+fn ping_verbose_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let (result, events) = ping::Ping::new(addr)
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_secs(1))
+        .ping_verbose();
+    result.unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn accept_predicate() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_millis(300);
+    ping::Ping::new(addr)
+        .timeout(timeout)
+        .accept(move |_, peer| peer.ip() == addr)
+        .ping()
+        .unwrap();
+
+    let result = ping::Ping::new(addr)
+        .timeout(timeout)
+        .accept(|_, _| false)
+        .ping();
+    assert!(result.unwrap_err().is_timeout());
+}
+
+#[test]
+fn burst_until_confident() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let target = ping::ConfidenceTarget {
+        half_width: Duration::from_millis(10),
+        max_probes: 20,
+        ..Default::default()
+    };
+    let burst = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .burst_until_confident(Duration::from_millis(10), &target);
+    // loopback RTTs vary by microseconds, so the minimum number of replies suffices
+    assert_eq!(burst.results.len(), target.min_replies);
+    let half_width = burst
+        .statistics
+        .rtt_confidence_half_width(target.z)
+        .unwrap();
+    assert!(half_width <= target.half_width);
+}
+
+#[test]
+fn fcrdns_loopback() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let result = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .ping_fcrdns(Duration::from_secs(5))
+        .unwrap();
+    // resolvers name loopback differently, e.g. localhost.localdomain
+    let name = match result.fcrdns {
+        ping::Fcrdns::Confirmed { name } => name,
+        fcrdns => panic!("unexpected {:?}", fcrdns),
+    };
+    let addrs: Vec<IpAddr> = std::net::ToSocketAddrs::to_socket_addrs(&(name.as_str(), 0))
+        .unwrap()
+        .map(|addr| addr.ip())
+        .collect();
+    assert!(addrs.contains(&addr));
+}
+
+#[test]
+fn event_channel() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let stream = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .events(sender)
+        .stream(Duration::from_millis(10));
+    for result in stream.take(2) {
+        result.unwrap();
+    }
+
+    let events: Vec<ping::PingEvent> = receiver.try_iter().collect();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[0].kind, ping::EventKind::Sent);
+    assert!(matches!(events[1].kind, ping::EventKind::Reply { .. }));
+    assert_eq!(events[2].seq_cnt, 2);
+    assert!(events.iter().all(|event| event.addr == addr));
+}
+
+#[test]
+fn route_check() {
+    // the route to an unchanging target stays the same, so the checks neither get
+    // in the way of a reply nor turn a timeout into a route change
+    let reply = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_secs(1))
+        .route_check(Duration::from_millis(10))
+        .ping();
+    assert!(reply.is_ok());
+
+    let result = ping::Ping::new("192.0.2.99".parse().unwrap())
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_millis(300))
+        .route_check(Duration::from_millis(20))
+        .ping();
+    assert!(result.unwrap_err().is_timeout());
+}
+
+#[test]
+fn self_ping() {
+    // loopback and the addresses of the host's uplinks, where it has any
+    let mut addrs: Vec<IpAddr> = vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+    addrs.extend(local_addr_towards("8.8.8.8:53"));
+    addrs.extend(local_addr_towards("[2001:4860:4860::8888]:53"));
+    for addr in addrs {
+        for socket_type in &[ping::Type::DGRAM, ping::Type::RAW] {
+            let reply = ping::Ping::new(addr)
+                .socket_type(*socket_type)
+                .timeout(Duration::from_secs(1))
+                .ping()
+                .unwrap();
+            assert!(reply.rtt < Duration::from_millis(100));
+
+            let result = ping::Ping::new(addr)
+                .socket_type(*socket_type)
+                .allow_self(false)
+                .ping();
+            assert!(
+                matches!(result, Err(ping::Error::LocalTarget { addr: local }) if local == addr)
+            );
+        }
+    }
+
+    let result = ping::Ping::new("192.0.2.99".parse().unwrap())
+        .timeout(Duration::from_millis(100))
+        .allow_self(false)
+        .ping();
+    assert!(result.unwrap_err().is_timeout());
+}
+
+#[test]
+fn send_wallclock() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let reply = ping::Ping::new(addr).ping().unwrap();
+    assert_eq!(reply.send_wallclock, None);
+
+    let before = std::time::SystemTime::now();
+    let reply = ping::Ping::new(addr).send_wallclock(true).ping().unwrap();
+    let after = std::time::SystemTime::now();
+    let send_wallclock = reply.send_wallclock.unwrap();
+    assert!(before <= send_wallclock && send_wallclock <= after);
+}
+
+#[test]
+fn ping_many_configured() {
+    let pings = vec![
+        ping::Ping::new("127.0.0.1".parse().unwrap()).ttl(1),
+        ping::Ping::new("::1".parse().unwrap()).socket_type(ping::Type::RAW),
+        ping::Ping::new("192.0.2.99".parse().unwrap()).timeout(Duration::from_millis(100)),
+        ping::Ping::new("127.0.0.1".parse().unwrap()).payload_size(100),
+    ];
+    let outcomes = ping::ping_many_configured(pings);
+    assert_eq!(outcomes.len(), 4);
+    assert!(outcomes[0].is_reply());
+    assert!(outcomes[1].is_reply());
+    assert!(matches!(outcomes[2], ping::PingOutcome::NoReply));
+    assert!(outcomes[3].is_reply());
+}
+
+#[test]
+fn burst_inter_arrival_times() {
+    let burst = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .burst(3, Duration::from_millis(50));
+    let inter_arrival_times = burst.inter_arrival_times();
+    assert_eq!(inter_arrival_times.len(), 2);
+    for time in inter_arrival_times {
+        assert!(time >= Duration::from_millis(40) && time < Duration::from_millis(500));
+    }
+}
+
+#[test]
+fn cancel_batch_mid_window() {
+    // nothing answers, so every ping of the window waits until cancelled
+    let addrs: Vec<IpAddr> = (90..96)
+        .map(|host| format!("192.0.2.{}", host).parse().unwrap())
+        .collect();
+    let token = ping::CancelToken::new();
+    let batch = ping::Batch::new().concurrency(2).configure(|ping| {
+        ping.timeout(Duration::from_secs(5))
+            .cancel_token(token.clone())
+    });
+    let canceller = {
+        let token = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        })
+    };
+    let start = std::time::Instant::now();
+    let outcomes = batch.ping_many(&addrs);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    canceller.join().unwrap();
+    assert_eq!(outcomes.len(), addrs.len());
+    assert!(outcomes
+        .iter()
+        .all(|outcome| matches!(outcome, ping::PingOutcome::Error(ping::Error::Cancelled))));
+
+    // the sent probe of a cancelled stream is resolved in its recording
+    let token = ping::CancelToken::new();
+    let mut stream = ping::Ping::new("192.0.2.90".parse().unwrap())
+        .timeout(Duration::from_secs(5))
+        .cancel_token(token.clone())
+        .stream(Duration::from_secs(1))
+        .record(8);
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        token.cancel();
+    });
+    assert!(stream.next().is_none());
+    canceller.join().unwrap();
+    let kinds: Vec<_> = stream
+        .recorder()
+        .unwrap()
+        .events()
+        .map(|event| event.kind.clone())
+        .collect();
+    assert_eq!(kinds.len(), 2);
+    assert_eq!(kinds[0], ping::EventKind::Sent);
+    assert!(matches!(&kinds[1], ping::EventKind::Error { message, .. } if message == "cancelled"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn decide_match() {
+    use ping::{MatchDecision, MatchStrategy, RequestMeta};
+
+    let echo_reply = |ident: u16, seq_cnt: u16, payload: &[u8]| {
+        let mut buffer = vec![0, 0, 0, 0];
+        buffer.extend_from_slice(&ident.to_be_bytes());
+        buffer.extend_from_slice(&seq_cnt.to_be_bytes());
+        buffer.extend_from_slice(payload);
+        ping::write_checksum(&mut buffer);
+        buffer
+    };
+    let request = RequestMeta {
+        is_ipv4: true,
+        socket_type: ping::Type::DGRAM,
+        ident: 7,
+        seq_cnt: 3,
+        payload: b"payload",
+    };
+    let sender = "192.0.2.1".parse().unwrap();
+    let strategy = MatchStrategy::default();
+    let decide = |reply: &[u8], strategy: &MatchStrategy| {
+        ping::decide_match(&request, reply, sender, strategy)
+    };
+
+    assert!(matches!(
+        decide(&echo_reply(7, 3, b"payload"), &strategy),
+        MatchDecision::Matched {
+            ttl: None,
+            checksum_valid: true,
+            ..
+        }
+    ));
+    assert!(matches!(
+        decide(&echo_reply(8, 3, b"payload"), &strategy),
+        MatchDecision::Foreign
+    ));
+    assert!(matches!(
+        decide(&echo_reply(7, 2, b"payload"), &strategy),
+        MatchDecision::Duplicate
+    ));
+
+    let verify_payload = MatchStrategy {
+        verify_payload: true,
+        ..MatchStrategy::default()
+    };
+    assert!(matches!(
+        decide(&echo_reply(7, 3, b"other"), &strategy),
+        MatchDecision::Matched { .. }
+    ));
+    assert!(matches!(
+        decide(&echo_reply(7, 3, b"other"), &verify_payload),
+        MatchDecision::Foreign
+    ));
+
+    // raw IPv4 sockets receive the IP header, which has to decode
+    let raw = RequestMeta {
+        socket_type: ping::Type::RAW,
+        ..request
+    };
+    assert!(matches!(
+        ping::decide_match(&raw, &[0x45, 0], sender, &strategy),
+        MatchDecision::DecodeError
+    ));
+}
+
+#[test]
+fn measure_setup() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let reply = ping::Ping::new(addr).ping().unwrap();
+    assert_eq!(reply.setup_time, None);
+
+    let reply = ping::Ping::new(addr).measure_setup(true).ping().unwrap();
+    assert!(reply.setup_time.unwrap() < Duration::from_secs(1));
+
+    // a reused socket only needs configuring
+    let socket = ping::PingSocket::new(ping::Type::DGRAM);
+    let reply = socket
+        .ping(ping::Ping::new(addr).measure_setup(true))
+        .unwrap();
+    assert!(reply.setup_time.is_some());
+}
+
+/// The first of `count` consecutive ICMP DGRAM ports that are free right now,
+/// starting at a port the kernel picked for an unbound socket.
+fn free_dgram_ports(count: u16) -> u16 {
+    let addr: IpAddr = "127.0.0.1".parse().unwrap();
+    let ping_from = |ports| {
+        ping::Ping::new(addr)
+            .socket_type(ping::Type::DGRAM)
+            .source_ports(ports)
+            .ping()
+    };
+    for _ in 0..16 {
+        let unbound = ping::Ping::new(addr)
+            .socket_type(ping::Type::DGRAM)
+            .ping()
+            .unwrap();
+        let first = unbound.ident;
+        if first.checked_add(count - 1).is_some()
+            && (first..=first + (count - 1)).all(|port| ping_from(port..=port).is_ok())
+        {
+            return first;
+        }
+    }
+    panic!("no {} consecutive free ICMP ports", count);
+}
+
+#[test]
+fn dgram_source_ports() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let first = free_dgram_ports(4);
+    let ports = first..=first + 3;
+    for _ in 0..4 {
+        let reply = ping::Ping::new(addr)
+            .socket_type(ping::Type::DGRAM)
+            .source_ports(ports.clone())
+            .ping()
+            .unwrap();
+        assert!(ports.contains(&reply.ident));
+    }
+
+    // every port of the range is in use by a ping waiting for its reply
+    let taken = free_dgram_ports(2);
+    let waiting = std::thread::spawn(move || {
+        ping::Ping::new("192.0.2.99".parse().unwrap())
+            .socket_type(ping::Type::DGRAM)
+            .timeout(Duration::from_millis(500))
+            .source_ports(taken..=taken)
+            .ping()
+    });
+    std::thread::sleep(Duration::from_millis(100));
+    let result = ping::Ping::new(addr)
+        .socket_type(ping::Type::DGRAM)
+        .source_ports(taken..=taken)
+        .ping();
+    match result {
+        Err(ping::Error::IoError { error }) => {
+            assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse)
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+    // taken ports are skipped
+    let reply = ping::Ping::new(addr)
+        .socket_type(ping::Type::DGRAM)
+        .source_ports(taken..=taken + 1)
+        .ping()
+        .unwrap();
+    assert_eq!(reply.ident, taken + 1);
+    assert!(waiting.join().unwrap().unwrap_err().is_timeout());
+
+    // raw sockets have no ports
+    let reply = ping::Ping::new(addr)
+        .socket_type(ping::Type::RAW)
+        .ident(7)
+        .source_ports(ports)
+        .ping()
+        .unwrap();
+    assert_eq!(reply.ident, 7);
+}
+
+#[test]
+fn monitor() {
+    let target = |addr: &str, interval| {
+        let ping = ping::Ping::new(addr.parse().unwrap()).timeout(Duration::from_millis(100));
+        ping::MonitorTarget::new(ping, interval)
+    };
+    let mut monitor = ping::Monitor::new(vec![
+        target("127.0.0.1", Duration::from_millis(20)),
+        target("192.0.2.99", Duration::from_millis(20)),
+    ]);
+    monitor.add(target("::1", Duration::from_millis(50)));
+    std::thread::sleep(Duration::from_millis(400));
+
+    let snapshot = monitor.snapshot();
+    assert_eq!(snapshot.len(), 3);
+    let local: IpAddr = "127.0.0.1".parse().unwrap();
+    let health = &snapshot[&local];
+    assert_eq!(health.status, Some(ping::HealthStatus::Up));
+    assert!(health.last_rtt.is_some());
+    assert_eq!(health.loss, 0.0);
+    assert!(health.probes > 1);
+    let silent: IpAddr = "192.0.2.99".parse().unwrap();
+    let health = &snapshot[&silent];
+    assert_eq!(health.status, Some(ping::HealthStatus::Down));
+    assert_eq!(health.last_rtt, None);
+    assert_eq!(health.loss, 1.0);
+
+    // removing a target waiting for its reply stops it quickly
+    let start = std::time::Instant::now();
+    assert!(monitor.remove(silent));
+    assert!(!monitor.remove(silent));
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(monitor.snapshot().len(), 2);
+
+    // the window only keeps the most recent probes
+    let mut windowed = target("127.0.0.1", Duration::from_millis(10));
+    windowed.window = 3;
+    monitor.add(windowed);
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(monitor.snapshot()[&local].probes, 3);
+}
+
+#[test]
+fn monitor_socket_options() {
+    // the bind address of the target's ping applies, so it cannot reach loopback
+    let ping = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .bind("192.0.2.99".parse().unwrap())
+        .timeout(Duration::from_millis(100));
+    let monitor = ping::Monitor::new(vec![ping::MonitorTarget::new(
+        ping,
+        Duration::from_millis(20),
+    )]);
+    std::thread::sleep(Duration::from_millis(200));
+    let health = &monitor.snapshot()[&"127.0.0.1".parse().unwrap()];
+    assert_eq!(health.status, Some(ping::HealthStatus::Down));
+    assert_eq!(health.loss, 1.0);
+}
+
+#[test]
+fn route_check_tiny_interval() {
+    // the wait until the next check is often below a microsecond, which must not
+    // turn into a read without time limit
+    let start = std::time::Instant::now();
+    let result = ping::Ping::new("192.0.2.99".parse().unwrap())
+        .socket_type(ping::Type::RAW)
+        .timeout(Duration::from_millis(300))
+        .route_check(Duration::from_nanos(1))
+        .ping();
+    assert!(result.unwrap_err().is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn route_check_zero_interval() {
+    // a zero interval disables the check instead of checking all the time
+    ping::Ping::new("127.0.0.1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .route_check(Duration::ZERO)
+        .ping()
+        .unwrap();
+}
+
+#[test]
+fn raw_icmp_size_errors() {
+    use ping::{IcmpMessage, IcmpReply};
+
+    let result = ping::RawIcmp::decode(ping::IcmpVersion::V4, &[8, 0]);
+    assert!(matches!(result, Err(ping::Error::DecodeIcmpError)));
+
+    let message = ping::RawIcmp {
+        icmp_type: 8,
+        icmp_code: 0,
+        body: vec![0; 8],
+    };
+    let mut buffer = [0; 4];
+    match message.encode(ping::IcmpVersion::V4, &mut buffer) {
+        Err(ping::Error::IoError { error }) => {
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput)
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn burst_health() {
+    let addr = "127.0.0.1".parse().unwrap();
+    let burst = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .burst(2, Duration::from_millis(10));
+    assert_eq!(burst.health, ping::HealthStatus::Up);
+
+    // nothing on loopback is this fast
+    let thresholds = ping::HealthThresholds {
+        degraded_rtt: Duration::ZERO,
+        ..ping::HealthThresholds::default()
+    };
+    let burst = ping::Ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .health_thresholds(thresholds)
+        .burst(2, Duration::from_millis(10));
+    assert_eq!(burst.health, ping::HealthStatus::Degraded);
+}
+
+#[test]
+fn invalid_confidence_quantile() {
+    let burst = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .burst(3, Duration::from_millis(10));
+    assert!(burst.statistics.rtt_confidence_half_width(1.96).is_some());
+    assert_eq!(burst.statistics.rtt_confidence_half_width(-1.0), None);
+    assert_eq!(burst.statistics.rtt_confidence_half_width(f64::NAN), None);
+
+    let target = ping::ConfidenceTarget {
+        z: f64::NAN,
+        max_probes: 3,
+        ..ping::ConfidenceTarget::default()
+    };
+    let burst = ping::Ping::new("127.0.0.1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .burst_until_confident(Duration::from_millis(10), &target);
+    assert_eq!(burst.results.len(), 3);
+}